@@ -1,11 +1,21 @@
 //! MCP server exposing Perplexity AI tools for search, research, and reasoning.
 
+mod metrics;
 mod server;
 
-use perplexity_web_api::{AuthCookies, Client, ReasonModel, SearchModel};
+use perplexity_web_api::{
+    AttachmentCache, AuthCookies, Client, RateLimit, RateLimiter, ReasonModel, ResponseCache,
+    SearchMode, SearchModel, UploadPolicy,
+};
 use rmcp::{ServiceExt, transport::stdio};
+use std::time::Duration;
 use std::{env, env::VarError};
+use tracing::Level;
+use tracing_subscriber::filter::Targets;
 use tracing_subscriber::fmt;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 
 use crate::server::PerplexityServer;
 
@@ -41,6 +51,17 @@ async fn shutdown_signal() {
     }
 }
 
+/// Serves the tool call metrics in the Prometheus text format.
+#[cfg(feature = "streamable-http")]
+async fn metrics(
+    axum::extract::State(server): axum::extract::State<PerplexityServer>,
+) -> impl axum::response::IntoResponse {
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        server.render_metrics(),
+    )
+}
+
 /// Reads an optional string environment variable, returning `None` if not present.
 fn optional_env(name: &str) -> Result<Option<String>, std::io::Error> {
     match env::var(name) {
@@ -55,28 +76,35 @@ fn optional_env(name: &str) -> Result<Option<String>, std::io::Error> {
     }
 }
 
-/// Reads an optional default model from environment.
-fn optional_model_env<T>(name: &str) -> Result<Option<T>, std::io::Error>
+/// Reads an optional comma-separated model chain from environment.
+///
+/// Returns an empty chain if the variable is not present.
+fn optional_model_chain_env<T>(name: &str) -> Result<Vec<T>, std::io::Error>
 where
     T: std::str::FromStr,
     T::Err: std::fmt::Display,
 {
-    match env::var(name) {
-        Ok(value) => {
-            let trimmed = value.trim();
-            if trimmed.is_empty() {
-                return Ok(None);
-            }
+    optional_env(name)?
+        .as_deref()
+        .map_or(Ok(Vec::new()), |value| parse_model_chain(name, value))
+}
 
-            trimmed.parse::<T>().map(Some).map_err(|e| {
+/// Parses a comma-separated model chain, e.g. `claude-4.6-sonnet,gpt-5.4`.
+fn parse_model_chain<T>(name: &str, value: &str) -> Result<Vec<T>, std::io::Error>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|model| !model.is_empty())
+        .map(|model| {
+            model.parse::<T>().map_err(|e| {
                 std::io::Error::other(format!("Invalid environment variable {name}: {e}"))
             })
-        }
-        Err(VarError::NotPresent) => Ok(None),
-        Err(VarError::NotUnicode(_)) => Err(std::io::Error::other(format!(
-            "Environment variable {name} must be valid UTF-8"
-        ))),
-    }
+        })
+        .collect()
 }
 
 /// Reads an optional boolean environment variable, returning `default` if not present.
@@ -96,21 +124,205 @@ fn parse_bool_env(name: &str, value: &str) -> Result<bool, std::io::Error> {
     }
 }
 
+/// Default maximum size of a single attached file (50 MiB).
+const DEFAULT_MAX_FILE_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Default maximum combined size of the files attached to one request (100 MiB).
+const DEFAULT_MAX_UPLOAD_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Default maximum number of files attached to one request.
+const DEFAULT_MAX_FILES: usize = 10;
+
+/// Reads an optional size environment variable, returning `default` if not present.
+fn optional_size_env(name: &str, default: u64) -> Result<u64, std::io::Error> {
+    optional_env(name)?.as_deref().map_or(Ok(default), |value| parse_size(name, value))
+}
+
+/// Parses a size in bytes with an optional `K`, `M` or `G` suffix (powers of 1024).
+fn parse_size(name: &str, value: &str) -> Result<u64, std::io::Error> {
+    let (digits, multiplier) = match value.as_bytes().last() {
+        Some(b'k' | b'K') => (&value[..value.len() - 1], 1024),
+        Some(b'm' | b'M') => (&value[..value.len() - 1], 1024 * 1024),
+        Some(b'g' | b'G') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|size| size.checked_mul(multiplier))
+        .ok_or_else(|| {
+            std::io::Error::other(format!(
+                "Invalid environment variable {name}: expected a size like 1048576, 512K or 25M"
+            ))
+        })
+}
+
+/// Builds the upload policy from environment, with limits suited to base64 tool input.
+fn upload_policy_from_env() -> Result<UploadPolicy, std::io::Error> {
+    let max_files = match optional_env("PERPLEXITY_MAX_FILES")? {
+        Some(value) => value.parse().map_err(|_| {
+            std::io::Error::other(
+                "Invalid environment variable PERPLEXITY_MAX_FILES: expected a number",
+            )
+        })?,
+        None => DEFAULT_MAX_FILES,
+    };
+    let mut policy = UploadPolicy::new()
+        .max_file_size(optional_size_env("PERPLEXITY_MAX_FILE_SIZE", DEFAULT_MAX_FILE_SIZE)?)
+        .max_batch_size(optional_size_env(
+            "PERPLEXITY_MAX_UPLOAD_SIZE",
+            DEFAULT_MAX_UPLOAD_SIZE,
+        )?)
+        .max_files(max_files);
+    if let Some(types) = optional_env("PERPLEXITY_ALLOWED_CONTENT_TYPES")? {
+        for content_type in types.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            policy = policy.allow_content_type(content_type);
+        }
+    }
+    Ok(policy)
+}
+
+/// Builds the attachment cache from environment; disabled unless a TTL is set.
+fn attachment_cache_from_env() -> Result<Option<AttachmentCache>, std::io::Error> {
+    let ttl_secs = match optional_env("PERPLEXITY_ATTACHMENT_CACHE_TTL")? {
+        Some(value) => value.parse::<u64>().map_err(|_| {
+            std::io::Error::other(
+                "Invalid environment variable PERPLEXITY_ATTACHMENT_CACHE_TTL: \
+                 expected a number of seconds",
+            )
+        })?,
+        None => 0,
+    };
+    if ttl_secs == 0 {
+        return Ok(None);
+    }
+    let ttl = Duration::from_secs(ttl_secs);
+    Ok(Some(match optional_env("PERPLEXITY_ATTACHMENT_CACHE_DIR")? {
+        Some(dir) => AttachmentCache::on_disk(dir, ttl),
+        None => AttachmentCache::in_memory(ttl),
+    }))
+}
+
+/// Default number of responses kept by the in-memory response cache.
+const DEFAULT_RESPONSE_CACHE_SIZE: usize = 256;
+
+/// Builds the response cache from environment; disabled unless a TTL is set.
+fn response_cache_from_env() -> Result<Option<ResponseCache>, std::io::Error> {
+    let ttl_secs = match optional_env("PERPLEXITY_RESPONSE_CACHE_TTL")? {
+        Some(value) => value.parse::<u64>().map_err(|_| {
+            std::io::Error::other(
+                "Invalid environment variable PERPLEXITY_RESPONSE_CACHE_TTL: \
+                 expected a number of seconds",
+            )
+        })?,
+        None => 0,
+    };
+    if ttl_secs == 0 {
+        return Ok(None);
+    }
+    let ttl = Duration::from_secs(ttl_secs);
+    if let Some(dir) = optional_env("PERPLEXITY_RESPONSE_CACHE_DIR")? {
+        return Ok(Some(ResponseCache::on_disk(dir, ttl)));
+    }
+    let size = match optional_env("PERPLEXITY_RESPONSE_CACHE_SIZE")? {
+        Some(value) => value.parse::<usize>().map_err(|_| {
+            std::io::Error::other(
+                "Invalid environment variable PERPLEXITY_RESPONSE_CACHE_SIZE: \
+                 expected a number of responses",
+            )
+        })?,
+        None => DEFAULT_RESPONSE_CACHE_SIZE,
+    };
+    Ok(Some(ResponseCache::in_memory(size, ttl)))
+}
+
+/// Reads an optional number of requests per minute from environment.
+fn optional_rate_env(name: &str) -> Result<Option<RateLimit>, std::io::Error> {
+    optional_env(name)?
+        .map(|value| {
+            value.parse::<u32>().map(RateLimit::per_minute).map_err(|_| {
+                std::io::Error::other(format!(
+                    "Invalid environment variable {name}: expected a number of requests per minute"
+                ))
+            })
+        })
+        .transpose()
+}
+
+/// Builds the rate limiter from environment; `None` when no budget is set.
+///
+/// Pro and reasoning searches share the Pro budget, since both spend Pro searches.
+fn rate_limiter_from_env() -> Result<Option<RateLimiter>, std::io::Error> {
+    let default = optional_rate_env("PERPLEXITY_RATE_LIMIT")?;
+    let pro = optional_rate_env("PERPLEXITY_PRO_RATE_LIMIT")?;
+    let research = optional_rate_env("PERPLEXITY_RESEARCH_RATE_LIMIT")?;
+    if default.is_none() && pro.is_none() && research.is_none() {
+        return Ok(None);
+    }
+
+    let mut limiter = RateLimiter::new();
+    if let Some(limit) = default {
+        limiter = limiter.limit(limit);
+    }
+    if let Some(limit) = pro {
+        limiter = limiter.shared_limit([SearchMode::Pro, SearchMode::Reasoning], limit);
+    }
+    if let Some(limit) = research {
+        limiter = limiter.mode_limit(SearchMode::DeepResearch, limit);
+    }
+    if let Some(value) = optional_env("PERPLEXITY_RATE_LIMIT_MAX_WAIT")? {
+        let secs = value.parse::<u64>().map_err(|_| {
+            std::io::Error::other(
+                "Invalid environment variable PERPLEXITY_RATE_LIMIT_MAX_WAIT: \
+                 expected a number of seconds",
+            )
+        })?;
+        limiter = limiter.max_wait(Duration::from_secs(secs));
+    }
+    Ok(Some(limiter))
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing (logs to stderr to not interfere with stdio transport)
+    // Initialize tracing (logs to stderr to not interfere with stdio transport).
+    // With PERPLEXITY_TRACE, the client's spans are logged with their timings.
+    let trace = optional_bool_env("PERPLEXITY_TRACE", false)?;
+    let mut targets = Targets::new().with_default(Level::INFO);
+    if trace {
+        targets = targets.with_target("perplexity_web_api", Level::DEBUG);
+    }
     fmt()
-        .with_max_level(tracing::Level::INFO)
+        .with_max_level(Level::DEBUG)
         .with_writer(std::io::stderr)
         .with_ansi(false)
+        .with_span_events(if trace { FmtSpan::CLOSE } else { FmtSpan::NONE })
+        .finish()
+        .with(targets)
         .init();
 
     let session_token = optional_env("PERPLEXITY_SESSION_TOKEN")?;
     let csrf_token = optional_env("PERPLEXITY_CSRF_TOKEN")?;
     let tokenless = session_token.is_none() || csrf_token.is_none();
     let incognito = optional_bool_env("PERPLEXITY_INCOGNITO", true)?;
+    let upload_policy = upload_policy_from_env()?;
+    let attachment_cache = attachment_cache_from_env()?;
+    let rate_limiter = rate_limiter_from_env()?;
+    let response_cache = response_cache_from_env()?;
+    let downgrade_research = match optional_env("PERPLEXITY_QUOTA_EXHAUSTED")?.as_deref() {
+        None | Some("refuse") => false,
+        Some("downgrade") => true,
+        Some(_) => {
+            return Err(std::io::Error::other(
+                "Invalid environment variable PERPLEXITY_QUOTA_EXHAUSTED: \
+                 expected refuse or downgrade",
+            )
+            .into());
+        }
+    };
+    let space = optional_env("PERPLEXITY_SPACE")?;
 
-    let (default_ask_model, default_reason_model) = if tokenless {
+    let (ask_models, reason_models) = if tokenless {
         // In tokenless mode, model overrides are not supported.
         if env::var("PERPLEXITY_ASK_MODEL").is_ok() {
             return Err(std::io::Error::other(
@@ -121,6 +333,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             )
             .into());
         }
+        if space.is_some() {
+            tracing::warn!("PERPLEXITY_SPACE is ignored without authentication tokens");
+        }
         if env::var("PERPLEXITY_REASON_MODEL").is_ok() {
             return Err(std::io::Error::other(
                 "PERPLEXITY_REASON_MODEL cannot be used without authentication tokens.\n\n\
@@ -130,12 +345,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             )
             .into());
         }
-        (Some(SearchModel::Turbo), None)
+        (vec![SearchModel::Turbo], Vec::new())
     } else {
-        let ask = optional_model_env::<SearchModel>("PERPLEXITY_ASK_MODEL")?
-            .unwrap_or(SearchModel::ProAuto);
-        let reason = optional_model_env::<ReasonModel>("PERPLEXITY_REASON_MODEL")?;
-        (Some(ask), reason)
+        let mut ask = optional_model_chain_env::<SearchModel>("PERPLEXITY_ASK_MODEL")?;
+        if ask.is_empty() {
+            ask.push(SearchModel::ProAuto);
+        }
+        let reason = optional_model_chain_env::<ReasonModel>("PERPLEXITY_REASON_MODEL")?;
+        (ask, reason)
     };
 
     if tokenless {
@@ -151,10 +368,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         if incognito { "enabled" } else { "disabled" }
     );
 
-    let mut builder = Client::builder();
+    // Tool responses never include the raw event, so skip building it.
+    let mut builder = Client::builder().keep_raw(false).upload_policy(upload_policy);
     if let (Some(session), Some(csrf)) = (session_token, csrf_token) {
         builder = builder.cookies(AuthCookies::new(session, csrf));
     }
+    if let Some(cache) = attachment_cache {
+        builder = builder.attachment_cache(cache);
+    }
+    if let Some(limiter) = rate_limiter {
+        builder = builder.rate_limiter(limiter);
+    }
+    if let Some(cache) = &response_cache {
+        builder = builder.response_cache(cache.clone());
+    }
 
     let client = builder.build().await.map_err(|e| {
         tracing::error!("Failed to create Perplexity client: {}", e);
@@ -163,13 +390,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     tracing::info!("Perplexity client initialized");
 
-    let server = PerplexityServer::new(
-        client,
-        default_ask_model,
-        default_reason_model,
-        tokenless,
-        incognito,
-    );
+    let server =
+        PerplexityServer::new(client, ask_models, reason_models, tokenless, incognito)
+            .default_space(space)
+            .downgrade_research(downgrade_research);
 
     let transport = optional_env("MCP_TRANSPORT")?.unwrap_or_else(|| "stdio".to_owned());
 
@@ -196,15 +420,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let port = optional_env("MCP_PORT")?.unwrap_or_else(|| "8080".to_owned());
             let addr = format!("{host}:{port}");
 
+            let metrics_server = server.clone();
             let http_service = StreamableHttpService::new(
                 move || Ok(server.clone()),
                 LocalSessionManager::default().into(),
                 Default::default(),
             );
 
-            let app = axum::Router::new().nest_service("/mcp", http_service);
+            let app = axum::Router::new()
+                .nest_service("/mcp", http_service)
+                .route("/metrics", axum::routing::get(metrics))
+                .with_state(metrics_server);
             let listener = tokio::net::TcpListener::bind(&addr).await?;
-            tracing::info!("MCP server listening on http://{addr}/mcp");
+            tracing::info!("MCP server listening on http://{addr}/mcp, metrics on /metrics");
             axum::serve(listener, app).with_graceful_shutdown(shutdown_signal()).await?;
         }
         #[cfg(not(feature = "streamable-http"))]
@@ -226,12 +454,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    if let Some(cache) = response_cache {
+        let stats = cache.stats();
+        tracing::info!(
+            "Response cache served {} of {} searches ({:.0}% hit rate)",
+            stats.hits,
+            stats.hits + stats.misses,
+            stats.hit_rate() * 100.0
+        );
+    }
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_bool_env;
+    use super::{parse_bool_env, parse_model_chain, parse_size};
+    use perplexity_web_api::SearchModel;
 
     #[test]
     fn parses_truthy_values() {
@@ -259,10 +498,42 @@ mod tests {
         assert!(error.to_string().contains("TEST_BOOL"));
     }
 
+    #[test]
+    fn parses_model_chain() {
+        let chain =
+            parse_model_chain::<SearchModel>("TEST_MODEL", " claude-4.6-sonnet, gpt-5.4 ,")
+                .unwrap();
+        assert_eq!(chain, vec![SearchModel::Claude46Sonnet, SearchModel::Gpt54]);
+    }
+
+    #[test]
+    fn rejects_unknown_model_in_chain() {
+        let error =
+            parse_model_chain::<SearchModel>("TEST_MODEL", "gpt-5.4,unknown").unwrap_err();
+        assert!(error.to_string().contains("TEST_MODEL"));
+        assert!(error.to_string().contains("unknown"));
+    }
+
     fn optional_bool_env_value(
         value: Option<&str>,
         default: bool,
     ) -> Result<bool, std::io::Error> {
         value.map_or(Ok(default), |value| parse_bool_env("TEST_BOOL", value))
     }
+
+    #[test]
+    fn parses_sizes_with_suffix() {
+        assert_eq!(parse_size("TEST_SIZE", "1048576").unwrap(), 1_048_576);
+        assert_eq!(parse_size("TEST_SIZE", "512K").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("TEST_SIZE", "25m").unwrap(), 25 * 1024 * 1024);
+        assert_eq!(parse_size("TEST_SIZE", "2G").unwrap(), 2 * 1024 * 1024 * 1024);
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        for value in ["", "M", "ten", "-1", "99999999999G"] {
+            let error = parse_size("TEST_SIZE", value).unwrap_err();
+            assert!(error.to_string().contains("TEST_SIZE"));
+        }
+    }
 }
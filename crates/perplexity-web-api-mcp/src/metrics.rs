@@ -0,0 +1,266 @@
+//! Metrics of tool calls, exposed in the Prometheus text format on `/metrics`
+//! by the Streamable HTTP transport.
+
+use perplexity_web_api::Error;
+use rmcp::{ErrorData as McpError, model::ErrorCode};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the tool call duration histogram buckets, in seconds.
+const DURATION_BUCKETS: [f64; 10] =
+    [0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+/// Tool call metrics, shared by all clones and so by all MCP sessions.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    tools: BTreeMap<&'static str, ToolStats>,
+    upstream_errors: BTreeMap<&'static str, u64>,
+    upload_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+struct ToolStats {
+    calls: u64,
+    in_flight: u64,
+    errors: BTreeMap<&'static str, u64>,
+    /// Cumulative counts of the calls that finished within each bucket.
+    buckets: [u64; DURATION_BUCKETS.len()],
+    finished: u64,
+    duration_sum: f64,
+}
+
+/// Marks a tool call as in flight until dropped, so cancelled calls are not
+/// counted as running forever.
+struct InFlight<'a> {
+    metrics: &'a Metrics,
+    tool: &'static str,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.metrics.update(|inner| {
+            let stats = inner.tools.entry(self.tool).or_default();
+            stats.in_flight = stats.in_flight.saturating_sub(1);
+        });
+    }
+}
+
+impl Metrics {
+    /// Runs a call of `tool`, recording its duration and outcome.
+    pub async fn track<T>(
+        &self,
+        tool: &'static str,
+        call: impl Future<Output = Result<T, McpError>>,
+    ) -> Result<T, McpError> {
+        self.update(|inner| {
+            let stats = inner.tools.entry(tool).or_default();
+            stats.calls += 1;
+            stats.in_flight += 1;
+        });
+        let _in_flight = InFlight { metrics: self, tool };
+        let started = Instant::now();
+
+        let result = call.await;
+        self.record_call(tool, started.elapsed(), result.as_ref().err());
+        result
+    }
+
+    /// Counts an error returned by the Perplexity client.
+    pub fn record_upstream_error(&self, error: &Error) {
+        self.update(|inner| {
+            *inner.upstream_errors.entry(upstream_error_class(error)).or_default() += 1
+        });
+    }
+
+    /// Counts the bytes of a file uploaded to Perplexity's storage.
+    pub fn record_upload(&self, bytes: u64) {
+        self.update(|inner| inner.upload_bytes += bytes);
+    }
+
+    fn record_call(&self, tool: &'static str, elapsed: Duration, error: Option<&McpError>) {
+        let seconds = elapsed.as_secs_f64();
+        self.update(|inner| {
+            let stats = inner.tools.entry(tool).or_default();
+            for (count, bound) in stats.buckets.iter_mut().zip(DURATION_BUCKETS) {
+                if seconds <= bound {
+                    *count += 1;
+                }
+            }
+            stats.finished += 1;
+            stats.duration_sum += seconds;
+            if let Some(error) = error {
+                *stats.errors.entry(tool_error_class(error)).or_default() += 1;
+            }
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Inner)) {
+        f(&mut self.inner.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    /// Renders the metrics, along with the search and cache counters of
+    /// `client`, in the Prometheus text exposition format.
+    #[cfg(feature = "streamable-http")]
+    pub fn render(&self, client: &perplexity_web_api::Client) -> String {
+        use std::fmt::Write as _;
+
+        let inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut out = String::new();
+        let mut section = |name: &str, kind: &str, help: &str, body: String| {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+            out.push_str(&body);
+        };
+
+        let name = "perplexity_mcp_tool_calls_total";
+        let mut body = String::new();
+        for (tool, stats) in &inner.tools {
+            let _ = writeln!(body, "{name}{{tool=\"{tool}\"}} {}", stats.calls);
+        }
+        section(name, "counter", "Tool calls received.", body);
+
+        let name = "perplexity_mcp_tool_in_flight";
+        let mut body = String::new();
+        for (tool, stats) in &inner.tools {
+            let _ = writeln!(body, "{name}{{tool=\"{tool}\"}} {}", stats.in_flight);
+        }
+        section(name, "gauge", "Tool calls currently running.", body);
+
+        let name = "perplexity_mcp_tool_errors_total";
+        let mut body = String::new();
+        for (tool, stats) in &inner.tools {
+            for (class, count) in &stats.errors {
+                let _ = writeln!(body, "{name}{{tool=\"{tool}\",class=\"{class}\"}} {count}");
+            }
+        }
+        section(name, "counter", "Tool calls that failed, by error class.", body);
+
+        let name = "perplexity_mcp_tool_duration_seconds";
+        let mut body = String::new();
+        for (tool, stats) in &inner.tools {
+            for (count, bound) in stats.buckets.iter().zip(DURATION_BUCKETS) {
+                let _ =
+                    writeln!(body, "{name}_bucket{{tool=\"{tool}\",le=\"{bound}\"}} {count}");
+            }
+            let _ = writeln!(
+                body,
+                "{name}_bucket{{tool=\"{tool}\",le=\"+Inf\"}} {}",
+                stats.finished
+            );
+            let _ = writeln!(body, "{name}_sum{{tool=\"{tool}\"}} {}", stats.duration_sum);
+            let _ = writeln!(body, "{name}_count{{tool=\"{tool}\"}} {}", stats.finished);
+        }
+        section(name, "histogram", "Duration of finished tool calls.", body);
+
+        let name = "perplexity_mcp_upstream_errors_total";
+        let mut body = String::new();
+        for (class, count) in &inner.upstream_errors {
+            let _ = writeln!(body, "{name}{{class=\"{class}\"}} {count}");
+        }
+        section(name, "counter", "Errors returned by Perplexity, by class.", body);
+
+        let name = "perplexity_mcp_upload_bytes_total";
+        let body = format!("{name} {}\n", inner.upload_bytes);
+        section(name, "counter", "Bytes of files uploaded to Perplexity.", body);
+
+        let usage = client.usage();
+        let name = "perplexity_mcp_searches_total";
+        let mut body = String::new();
+        for (mode, count) in [
+            ("auto", usage.auto),
+            ("pro", usage.pro),
+            ("reasoning", usage.reasoning),
+            ("deep_research", usage.deep_research),
+        ] {
+            let _ = writeln!(body, "{name}{{mode=\"{mode}\"}} {count}");
+        }
+        section(name, "counter", "Searches sent to Perplexity, by mode.", body);
+
+        if let Some(stats) = client.response_cache_stats() {
+            let name = "perplexity_mcp_response_cache_hits_total";
+            let body = format!("{name} {}\n", stats.hits);
+            section(name, "counter", "Searches answered from the response cache.", body);
+
+            let name = "perplexity_mcp_response_cache_misses_total";
+            let body = format!("{name} {}\n", stats.misses);
+            section(name, "counter", "Searches not found in the response cache.", body);
+        }
+
+        out
+    }
+}
+
+/// Classifies a failed tool call by its MCP error code.
+fn tool_error_class(error: &McpError) -> &'static str {
+    if error.code == ErrorCode::INVALID_PARAMS {
+        "invalid_params"
+    } else if error.code == ErrorCode::INVALID_REQUEST {
+        "invalid_request"
+    } else {
+        "internal"
+    }
+}
+
+/// Classifies an error returned by the Perplexity client.
+fn upstream_error_class(error: &Error) -> &'static str {
+    match error {
+        Error::Timeout(_) => "timeout",
+        Error::RateLimited { .. } => "rate_limited",
+        Error::Server { status: 429, .. } => "throttled",
+        Error::Server { status, .. } if *status >= 500 => "server",
+        Error::Server { .. } => "rejected",
+        Error::AttachmentRejected { .. } => "attachment_rejected",
+        Error::AttachmentUnconfirmed { .. } => "attachment_unconfirmed",
+        error if error.is_upload_policy_violation() => "upload_policy",
+        Error::SessionWarmup(_)
+        | Error::SearchRequest(_)
+        | Error::UploadRequest(_)
+        | Error::UploadUrlFailed(_)
+        | Error::AttachmentProcessing(_)
+        | Error::UnexpectedEndOfStream => "network",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tracks_calls_and_errors() {
+        let metrics = Metrics::default();
+        let ok: Result<(), McpError> = metrics.track("perplexity_ask", async { Ok(()) }).await;
+        assert!(ok.is_ok());
+        let err: Result<(), McpError> = metrics
+            .track("perplexity_ask", async { Err(McpError::invalid_params("bad", None)) })
+            .await;
+        assert!(err.is_err());
+
+        let inner = metrics.inner.lock().unwrap();
+        let stats = &inner.tools["perplexity_ask"];
+        assert_eq!(stats.calls, 2);
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.finished, 2);
+        assert_eq!(stats.buckets[0], 2);
+        assert_eq!(stats.errors["invalid_params"], 1);
+    }
+
+    #[test]
+    fn classifies_upstream_errors() {
+        let server = |status| Error::Server { status, message: String::new() };
+        assert_eq!(upstream_error_class(&Error::Timeout(Duration::from_secs(1))), "timeout");
+        assert_eq!(upstream_error_class(&server(429)), "throttled");
+        assert_eq!(upstream_error_class(&server(503)), "server");
+        assert_eq!(upstream_error_class(&server(403)), "rejected");
+        assert_eq!(
+            upstream_error_class(&Error::TooManyFiles { count: 3, limit: 2 }),
+            "upload_policy"
+        );
+        assert_eq!(upstream_error_class(&Error::NothingToRewrite), "other");
+    }
+}
@@ -1,15 +1,31 @@
 use base64::Engine as _;
 use perplexity_web_api::{
-    Client, ModelPreference, ReasonModel, SearchMode, SearchModel, SearchRequest,
-    SearchWebResult, Source, UploadFile,
+    Client, ModelPreference, Quota, ReasonModel, SearchMode, SearchModel, SearchRequest,
+    SearchWebResult, Source, UploadFile, UploadProgress, UploadStage,
 };
 use rmcp::{
-    ErrorData as McpError, ServerHandler,
+    ErrorData as McpError, RoleServer, ServerHandler,
     handler::server::wrapper::Parameters,
-    model::{CallToolResult, Content, Implementation, ServerCapabilities, ServerInfo},
-    schemars, tool, tool_handler, tool_router,
+    model::{
+        CallToolResult, Content, Implementation, ProgressNotificationParam,
+        ServerCapabilities, ServerInfo,
+    },
+    schemars,
+    service::RequestContext,
+    tool, tool_handler, tool_router,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::metrics::Metrics;
+
+/// How long a fetched quota is reused before research queries fetch it again.
+const QUOTA_TTL: Duration = Duration::from_secs(60);
+
+/// Mode, models and notice a research query is served with.
+type ResearchPlan = (SearchMode, Vec<ModelPreference>, Option<String>);
 
 /// A file to attach to the query for document analysis.
 /// Requires authentication tokens. Provide either `text` or `data`, not both.
@@ -27,6 +43,11 @@ pub struct FileAttachment {
     /// Mutually exclusive with `text`.
     #[serde(default)]
     pub data: Option<String>,
+
+    /// MIME type of the file, e.g. "image/png". Detected from the filename and
+    /// content when omitted.
+    #[serde(default)]
+    pub content_type: Option<String>,
 }
 
 /// Request parameters for `perplexity_search` (no file attachments).
@@ -65,14 +86,83 @@ pub struct PerplexityRequest {
     /// Each entry needs `filename` and either `text` (plain text) or `data` (base64 binary).
     #[serde(default)]
     pub files: Option<Vec<FileAttachment>>,
+
+    /// URLs of files attached to earlier answers (`follow_up.attachments`) to attach
+    /// again without re-uploading them. Requires authentication tokens.
+    #[serde(default)]
+    pub attachment_urls: Option<Vec<String>>,
+
+    /// UUID of a Perplexity Space to search within, applying its instructions and files.
+    /// Defaults to PERPLEXITY_SPACE when set. Requires authentication tokens.
+    #[serde(default)]
+    pub space: Option<String>,
 }
 
 impl From<PerplexitySearchRequest> for PerplexityRequest {
     fn from(r: PerplexitySearchRequest) -> Self {
-        Self { query: r.query, sources: r.sources, language: r.language, files: None }
+        Self {
+            query: r.query,
+            sources: r.sources,
+            language: r.language,
+            files: None,
+            attachment_urls: None,
+            space: None,
+        }
     }
 }
 
+/// Request parameters for `perplexity_compare`.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct PerplexityCompareRequest {
+    /// The search query or question to ask every model.
+    pub query: String,
+
+    /// Models to compare, e.g. ["gpt-5.4", "claude-4.6-sonnet"]. Accepts the values of
+    /// PERPLEXITY_ASK_MODEL and PERPLEXITY_REASON_MODEL.
+    pub models: Vec<String>,
+
+    /// Information sources to search. Valid values: "web", "scholar", "social".
+    /// Defaults to ["web"] if not specified.
+    #[serde(default)]
+    pub sources: Option<Vec<String>>,
+
+    /// Language code (ISO 639), e.g., "en-US". Defaults to "en-US".
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// Response from `perplexity_compare`.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct CompareResponse {
+    /// One result per requested model, in request order.
+    pub results: Vec<CompareModelResult>,
+
+    /// Source URLs cited by every model that answered, when at least two answered.
+    pub shared_urls: Vec<String>,
+}
+
+/// The answer of one model in `perplexity_compare`.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct CompareModelResult {
+    /// The model, as given in the request.
+    pub model: String,
+
+    /// The generated answer text.
+    pub answer: Option<String>,
+
+    /// Web search results/sources from the response.
+    pub web_results: Vec<SearchWebResult>,
+
+    /// Source URLs cited by this model and no other.
+    pub unique_urls: Vec<String>,
+
+    /// Milliseconds the model took to answer or fail.
+    pub elapsed_ms: u64,
+
+    /// Why the model failed to answer, if it did.
+    pub error: Option<String>,
+}
+
 /// Response from Perplexity tools.
 #[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 pub struct PerplexityResponse {
@@ -84,6 +174,11 @@ pub struct PerplexityResponse {
 
     /// Context for making follow-up queries.
     pub follow_up: FollowUpInfo,
+
+    /// Set when the query was answered differently than asked, e.g. with a Pro
+    /// search because the research quota is exhausted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notice: Option<String>,
 }
 
 /// Follow-up context information.
@@ -107,10 +202,32 @@ pub struct SearchOnlyResponse {
 #[derive(Clone)]
 pub struct PerplexityServer {
     client: Client,
-    ask_model: Option<SearchModel>,
-    reason_model: Option<ReasonModel>,
+    ask_models: Vec<SearchModel>,
+    reason_models: Vec<ReasonModel>,
     tokenless: bool,
     incognito: bool,
+    space: Option<String>,
+    downgrade_research: bool,
+    metrics: Metrics,
+    /// Last quota fetched and when, shared by all sessions.
+    quota: Arc<Mutex<Option<(Instant, Quota)>>>,
+}
+
+/// Parses a model name accepted by either the ask or the reason tool.
+fn parse_model_name(name: &str) -> Result<ModelPreference, McpError> {
+    name.parse::<SearchModel>()
+        .map(ModelPreference::from)
+        .or_else(|_| name.parse::<ReasonModel>().map(ModelPreference::from))
+        .map_err(|_| {
+            McpError::invalid_params(
+                format!(
+                    "Unknown model '{name}', expected one of: {}, {}",
+                    SearchModel::valid_names_csv(),
+                    ReasonModel::valid_names_csv()
+                ),
+                None,
+            )
+        })
 }
 
 fn to_json_tool_result(value: &impl Serialize) -> Result<CallToolResult, McpError> {
@@ -120,6 +237,98 @@ fn to_json_tool_result(value: &impl Serialize) -> Result<CallToolResult, McpErro
     Ok(CallToolResult::success(vec![Content::text(json)]))
 }
 
+/// Progress of an upload across both of its phases: each byte sent counts
+/// as one unit, and each file the server finished processing as one more.
+///
+/// MCP requires progress to increase with every notification, so updates
+/// that do not move it forward are dropped.
+#[derive(Debug)]
+struct UploadProgressTracker {
+    sizes: Vec<u64>,
+    sent: HashMap<usize, u64>,
+    finished: HashSet<usize>,
+    reported: Option<u64>,
+}
+
+impl UploadProgressTracker {
+    fn new(files: &[UploadFile]) -> Self {
+        Self {
+            sizes: files.iter().map(UploadFile::size).collect(),
+            sent: HashMap::new(),
+            finished: HashSet::new(),
+            reported: None,
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.sizes.iter().sum::<u64>() + self.sizes.len() as u64
+    }
+
+    /// Applies `update`, returning the new progress if it grew.
+    fn update(&mut self, update: &UploadProgress) -> Option<u64> {
+        match update.stage {
+            UploadStage::Sending { sent, .. } => {
+                self.sent.insert(update.index, sent);
+            }
+            UploadStage::Processed | UploadStage::Rejected => {
+                // Files found in the attachment cache are never sent, so
+                // count their bytes once they are done.
+                if let Some(&size) = self.sizes.get(update.index) {
+                    self.sent.insert(update.index, size);
+                }
+                self.finished.insert(update.index);
+            }
+            UploadStage::Uploaded | UploadStage::Processing => {}
+        }
+        let progress = self.sent.values().sum::<u64>() + self.finished.len() as u64;
+        if self.reported.is_some_and(|reported| progress <= reported) {
+            return None;
+        }
+        self.reported = Some(progress);
+        Some(progress)
+    }
+}
+
+/// Forwards upload progress to the MCP client as progress notifications.
+///
+/// Returns `None` when the client did not request progress. Progress counts
+/// the bytes sent across all files, then one unit per processed file.
+fn upload_progress_notifier(
+    context: &RequestContext<RoleServer>,
+    files: &[UploadFile],
+) -> Option<impl Fn(UploadProgress) + Send + Sync + 'static> {
+    let token = context.meta.get_progress_token()?;
+    let peer = context.peer.clone();
+    let mut tracker = UploadProgressTracker::new(files);
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<UploadProgress>();
+
+    tokio::spawn(async move {
+        #[allow(clippy::cast_precision_loss)]
+        let total = tracker.total() as f64;
+        while let Some(update) = rx.recv().await {
+            let Some(progress) = tracker.update(&update) else { continue };
+            let message = match update.stage {
+                UploadStage::Sending { .. } => format!("Uploading {}", update.filename),
+                UploadStage::Uploaded => format!("Uploaded {}", update.filename),
+                UploadStage::Processing => format!("Processing {}", update.filename),
+                UploadStage::Processed => format!("Processed {}", update.filename),
+                UploadStage::Rejected => format!("Rejected {}", update.filename),
+            };
+            #[allow(clippy::cast_precision_loss)]
+            let param = ProgressNotificationParam::new(token.clone(), progress as f64)
+                .with_total(total)
+                .with_message(message);
+            if let Err(e) = peer.notify_progress(param).await {
+                tracing::debug!("Failed to send upload progress notification: {}", e);
+            }
+        }
+    });
+
+    Some(move |update| {
+        let _ = tx.send(update);
+    })
+}
+
 impl PerplexityServer {
     /// Creates a new server instance with the given Perplexity client.
     ///
@@ -127,21 +336,83 @@ impl PerplexityServer {
     /// (both with the `turbo` model) are registered. The `perplexity_research` and
     /// `perplexity_reason` tools require authenticated session cookies and are
     /// removed from the router.
+    ///
+    /// `ask_models` and `reason_models` are ordered fallback chains; an empty
+    /// chain uses the mode's default model.
     pub fn new(
         client: Client,
-        ask_model: Option<SearchModel>,
-        reason_model: Option<ReasonModel>,
+        ask_models: Vec<SearchModel>,
+        reason_models: Vec<ReasonModel>,
         tokenless: bool,
         incognito: bool,
     ) -> Self {
-        Self { client, ask_model, reason_model, tokenless, incognito }
+        Self {
+            client,
+            ask_models,
+            reason_models,
+            tokenless,
+            incognito,
+            space: None,
+            downgrade_research: false,
+            metrics: Metrics::default(),
+            quota: Arc::default(),
+        }
+    }
+
+    /// Sets whether research queries fall back to a Pro search when the
+    /// research quota is exhausted, instead of being refused.
+    #[must_use]
+    pub fn downgrade_research(mut self, downgrade: bool) -> Self {
+        self.downgrade_research = downgrade;
+        self
+    }
+
+    /// Sets the Space that `perplexity_ask`, `perplexity_research` and
+    /// `perplexity_reason` search within unless a request names one.
+    ///
+    /// Tokenless servers cannot search a Space and ignore the default.
+    #[must_use]
+    pub fn default_space(mut self, space: Option<String>) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// Renders the metrics of the server's tool calls for Prometheus.
+    #[cfg(feature = "streamable-http")]
+    pub fn render_metrics(&self) -> String {
+        self.metrics.render(&self.client)
+    }
+
+    /// Converts a Perplexity client error into a tool error, counting it.
+    fn api_error(&self, error: &perplexity_web_api::Error) -> McpError {
+        self.metrics.record_upstream_error(error);
+        if error.is_upload_policy_violation() {
+            McpError::invalid_params(error.to_string(), None)
+        } else {
+            McpError::internal_error(format!("Perplexity API error: {}", error), None)
+        }
     }
 
     /// Converts a `FileAttachment` from tool parameters into an `UploadFile`.
-    fn convert_attachment(attachment: FileAttachment) -> Result<UploadFile, McpError> {
-        match (attachment.text, attachment.data) {
-            (Some(text), None) => Ok(UploadFile::from_text(attachment.filename, text)),
+    ///
+    /// Base64 data is checked against the maximum file size before it is decoded.
+    fn convert_attachment(&self, attachment: FileAttachment) -> Result<UploadFile, McpError> {
+        let file = match (attachment.text, attachment.data) {
+            (Some(text), None) => UploadFile::from_text(attachment.filename, text),
             (None, Some(b64)) => {
+                let decoded_len = b64.len() as u64 / 4 * 3;
+                if let Some(limit) = self.client.upload_policy().max_file_size
+                    && decoded_len > limit
+                {
+                    return Err(McpError::invalid_params(
+                        format!(
+                            "File '{}' is about {decoded_len} bytes, more than the allowed \
+                             {limit}",
+                            attachment.filename
+                        ),
+                        None,
+                    ));
+                }
                 let bytes =
                     base64::engine::general_purpose::STANDARD.decode(&b64).map_err(|e| {
                         McpError::invalid_params(
@@ -152,67 +423,158 @@ impl PerplexityServer {
                             None,
                         )
                     })?;
-                Ok(UploadFile::from_bytes(attachment.filename, bytes))
+                UploadFile::from_bytes(attachment.filename, bytes)
             }
-            (Some(_), Some(_)) => Err(McpError::invalid_params(
-                format!(
-                    "File '{}' has both `text` and `data` set; provide only one.",
-                    attachment.filename
-                ),
+            (Some(_), Some(_)) => {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "File '{}' has both `text` and `data` set; provide only one.",
+                        attachment.filename
+                    ),
+                    None,
+                ));
+            }
+            (None, None) => {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "File '{}' has neither `text` nor `data` set; provide one.",
+                        attachment.filename
+                    ),
+                    None,
+                ));
+            }
+        };
+        Ok(match attachment.content_type {
+            Some(content_type) => file.content_type(content_type),
+            None => file,
+        })
+    }
+
+    /// Runs the query of `params` against each requested model concurrently.
+    async fn do_compare(
+        &self,
+        params: PerplexityCompareRequest,
+    ) -> Result<CompareResponse, McpError> {
+        if self.tokenless {
+            return Err(McpError::invalid_params(
+                "Comparing models requires authentication tokens. \
+                 Set PERPLEXITY_SESSION_TOKEN and PERPLEXITY_CSRF_TOKEN.",
                 None,
-            )),
-            (None, None) => Err(McpError::invalid_params(
-                format!(
-                    "File '{}' has neither `text` nor `data` set; provide one.",
-                    attachment.filename
-                ),
+            ));
+        }
+        if params.models.is_empty() {
+            return Err(McpError::invalid_params(
+                "`models` must name at least one model",
                 None,
-            )),
+            ));
+        }
+        let models = params
+            .models
+            .iter()
+            .map(|name| parse_model_name(name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut request = SearchRequest::new(&params.query).incognito(self.incognito);
+        if let Some(sources) = params.sources {
+            let parsed_sources: Vec<Source> =
+                sources.iter().filter_map(|s| s.parse::<Source>().ok()).collect();
+            if !parsed_sources.is_empty() {
+                request = request.sources(parsed_sources);
+            }
         }
+        if let Some(language) = params.language {
+            request = request.language(language);
+        }
+        let comparison =
+            self.client.compare(request, &models).await.map_err(|e| self.api_error(&e))?;
+
+        let results = params
+            .models
+            .into_iter()
+            .zip(comparison.entries)
+            .map(|(model, entry)| {
+                let elapsed_ms = u64::try_from(entry.elapsed.as_millis()).unwrap_or(u64::MAX);
+                match entry.result {
+                    Ok(response) => CompareModelResult {
+                        model,
+                        answer: response.answer,
+                        web_results: response.web_results,
+                        unique_urls: entry.unique_urls,
+                        elapsed_ms,
+                        error: None,
+                    },
+                    Err(e) => {
+                        self.metrics.record_upstream_error(&e);
+                        CompareModelResult {
+                            model,
+                            answer: None,
+                            web_results: Vec::new(),
+                            unique_urls: Vec::new(),
+                            elapsed_ms,
+                            error: Some(e.to_string()),
+                        }
+                    }
+                }
+            })
+            .collect();
+
+        Ok(CompareResponse { results, shared_urls: comparison.shared_urls })
     }
 
     /// Helper to execute a search with the given mode.
     ///
     /// When `files_allowed` is `false`, the method rejects any request that
     /// contains file attachments with a clear error before doing anything else.
+    /// Upload progress is reported to the client when it provided a progress token.
     async fn do_search(
         &self,
+        context: &RequestContext<RoleServer>,
         params: PerplexityRequest,
         mode: SearchMode,
-        model_preference: Option<ModelPreference>,
+        models: Vec<ModelPreference>,
         files_allowed: bool,
     ) -> Result<PerplexityResponse, McpError> {
-        let files: Vec<UploadFile> = if let Some(attachments) = params.files {
-            if !attachments.is_empty() {
-                if !files_allowed {
-                    return Err(McpError::invalid_params(
-                        "This tool does not support file attachments. \
-                         Use perplexity_ask, perplexity_research, or perplexity_reason instead.",
-                        None,
-                    ));
-                }
-                if self.tokenless {
-                    return Err(McpError::invalid_params(
-                        "File attachments require authentication tokens. \
-                         Set PERPLEXITY_SESSION_TOKEN and PERPLEXITY_CSRF_TOKEN.",
-                        None,
-                    ));
-                }
-                attachments
-                    .into_iter()
-                    .map(Self::convert_attachment)
-                    .collect::<Result<Vec<_>, _>>()?
-            } else {
-                Vec::new()
-            }
-        } else {
-            Vec::new()
-        };
-
-        let has_files = !files.is_empty();
+        let attachments = params.files.unwrap_or_default();
+        let attachment_urls = params.attachment_urls.unwrap_or_default();
+        let has_attachments = !attachments.is_empty() || !attachment_urls.is_empty();
+
+        if has_attachments && !files_allowed {
+            return Err(McpError::invalid_params(
+                "This tool does not support file attachments. \
+                 Use perplexity_ask, perplexity_research, or perplexity_reason instead.",
+                None,
+            ));
+        }
+        if has_attachments && self.tokenless {
+            return Err(McpError::invalid_params(
+                "File attachments require authentication tokens. \
+                 Set PERPLEXITY_SESSION_TOKEN and PERPLEXITY_CSRF_TOKEN.",
+                None,
+            ));
+        }
+        let space = params.space;
+        if space.is_some() && self.tokenless {
+            return Err(McpError::invalid_params(
+                "Searching a Space requires authentication tokens. \
+                 Set PERPLEXITY_SESSION_TOKEN and PERPLEXITY_CSRF_TOKEN.",
+                None,
+            ));
+        }
+        if let Some(limit) = self.client.upload_policy().max_files
+            && attachments.len() > limit
+        {
+            return Err(McpError::invalid_params(
+                format!("{} files attached, more than the allowed {limit}", attachments.len()),
+                None,
+            ));
+        }
+        let files: Vec<UploadFile> = attachments
+            .into_iter()
+            .map(|attachment| self.convert_attachment(attachment))
+            .collect::<Result<Vec<_>, _>>()?;
 
         let effective_mode =
-            if mode == SearchMode::Auto && (model_preference.is_some() || has_files) {
+            if mode == SearchMode::Auto && (!models.is_empty() || has_attachments) {
                 SearchMode::Pro
             } else {
                 mode
@@ -221,13 +583,32 @@ impl PerplexityServer {
         let mut request =
             SearchRequest::new(&params.query).mode(effective_mode).incognito(self.incognito);
 
-        if let Some(model_preference) = model_preference {
-            request = request.model(model_preference);
+        if !models.is_empty() {
+            request = request.models(models);
+        }
+
+        if !files.is_empty() {
+            // Count the bytes of each file once storage accepted it, so
+            // refused and failed uploads are left out.
+            let sizes: Vec<u64> = files.iter().map(UploadFile::size).collect();
+            let metrics = self.metrics.clone();
+            let notifier = upload_progress_notifier(context, &files);
+            request = request.on_upload_progress(move |update| {
+                if update.stage == UploadStage::Uploaded
+                    && let Some(&size) = sizes.get(update.index)
+                {
+                    metrics.record_upload(size);
+                }
+                if let Some(notifier) = &notifier {
+                    notifier(update);
+                }
+            });
         }
 
         for file in files {
             request = request.file(file);
         }
+        request = request.attachment_urls(attachment_urls);
 
         if let Some(sources) = params.sources
             && !sources.is_empty()
@@ -243,9 +624,11 @@ impl PerplexityServer {
             request = request.language(language);
         }
 
-        let response = self.client.search(request).await.map_err(|e| {
-            McpError::internal_error(format!("Perplexity API error: {}", e), None)
-        })?;
+        if let Some(space) = space {
+            request = request.space(space);
+        }
+
+        let response = self.client.search(request).await.map_err(|e| self.api_error(&e))?;
         let perplexity_web_api::SearchResponse { answer, web_results, follow_up, .. } =
             response;
 
@@ -256,8 +639,92 @@ impl PerplexityServer {
                 backend_uuid: follow_up.backend_uuid,
                 attachments: follow_up.attachments,
             },
+            notice: None,
         })
     }
+
+    /// Fills in the default Space unless the request names one or the server
+    /// is tokenless.
+    fn with_default_space(&self, mut params: PerplexityRequest) -> PerplexityRequest {
+        if params.space.is_none() && !self.tokenless {
+            params.space.clone_from(&self.space);
+        }
+        params
+    }
+
+    /// Picks how to serve a research query given the account's quota.
+    ///
+    /// The query proceeds as research when the quota cannot be fetched.
+    async fn research_plan(&self) -> Result<ResearchPlan, McpError> {
+        if self.tokenless {
+            return plan_research(None, self.downgrade_research, &self.ask_models);
+        }
+        let quota = self.cached_quota().await;
+        plan_research(quota.as_ref(), self.downgrade_research, &self.ask_models)
+    }
+
+    /// Returns the account's quota, fetched at most once per [`QUOTA_TTL`].
+    async fn cached_quota(&self) -> Option<Quota> {
+        let cached = *self.quota.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((fetched, quota)) = cached
+            && fetched.elapsed() < QUOTA_TTL
+        {
+            return Some(quota);
+        }
+        match self.client.quota().await {
+            Ok(quota) => {
+                *self.quota.lock().unwrap_or_else(PoisonError::into_inner) =
+                    Some((Instant::now(), quota));
+                Some(quota)
+            }
+            Err(e) => {
+                tracing::warn!("Failed to fetch Perplexity quota: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Decides how to serve a research query given the account's `quota`, if
+/// known.
+///
+/// Returns the mode and models to search with and a notice when the query
+/// is downgraded. Fails when the research quota is exhausted and the query
+/// may not, or cannot, be downgraded.
+fn plan_research(
+    quota: Option<&Quota>,
+    downgrade: bool,
+    ask_models: &[SearchModel],
+) -> Result<ResearchPlan, McpError> {
+    let research = (SearchMode::DeepResearch, Vec::new(), None);
+    let Some(quota) = quota else {
+        return Ok(research);
+    };
+    if !quota.is_exhausted(SearchMode::DeepResearch) {
+        return Ok(research);
+    }
+    if !downgrade {
+        return Err(McpError::invalid_request(
+            "The deep research quota of this Perplexity account is exhausted. \
+                 Use perplexity_ask or perplexity_reason instead, or try again later.",
+            None,
+        ));
+    }
+    if quota.is_exhausted(SearchMode::Pro) {
+        return Err(McpError::invalid_request(
+            "The deep research and Pro quotas of this Perplexity account are exhausted. \
+                 Try again later.",
+            None,
+        ));
+    }
+    Ok((
+        SearchMode::Pro,
+        ask_models.iter().copied().map(ModelPreference::from).collect(),
+        Some(
+            "The deep research quota is exhausted, so this was answered with a Pro search."
+                .to_owned(),
+        ),
+    ))
 }
 
 #[tool_router]
@@ -283,9 +750,16 @@ impl PerplexityServer {
     pub async fn perplexity_search(
         &self,
         Parameters(params): Parameters<PerplexitySearchRequest>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
-        let response = self.do_search(params.into(), SearchMode::Auto, None, false).await?;
-        to_json_tool_result(&SearchOnlyResponse { web_results: response.web_results })
+        self.metrics
+            .track("perplexity_search", async {
+                let response = self
+                    .do_search(&context, params.into(), SearchMode::Auto, Vec::new(), false)
+                    .await?;
+                to_json_tool_result(&SearchOnlyResponse { web_results: response.web_results })
+            })
+            .await
     }
 
     /// Ask Perplexity AI a question and get an answer with sources.
@@ -310,16 +784,22 @@ impl PerplexityServer {
     pub async fn perplexity_ask(
         &self,
         Parameters(params): Parameters<PerplexityRequest>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
-        let response = self
-            .do_search(
-                params,
-                SearchMode::Auto,
-                self.ask_model.map(ModelPreference::from),
-                true,
-            )
-            .await?;
-        to_json_tool_result(&response)
+        self.metrics
+            .track("perplexity_ask", async {
+                let response = self
+                    .do_search(
+                        &context,
+                        self.with_default_space(params),
+                        SearchMode::Auto,
+                        self.ask_models.iter().copied().map(ModelPreference::from).collect(),
+                        true,
+                    )
+                    .await?;
+                to_json_tool_result(&response)
+            })
+            .await
     }
 
     /// Deep, comprehensive research using Perplexity's sonar-deep-research model.
@@ -346,10 +826,18 @@ impl PerplexityServer {
     pub async fn perplexity_research(
         &self,
         Parameters(params): Parameters<PerplexityRequest>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
-        to_json_tool_result(
-            &self.do_search(params, SearchMode::DeepResearch, None, true).await?,
-        )
+        self.metrics
+            .track("perplexity_research", async {
+                let (mode, models, notice) = self.research_plan().await?;
+                let mut response = self
+                    .do_search(&context, self.with_default_space(params), mode, models, true)
+                    .await?;
+                response.notice = notice;
+                to_json_tool_result(&response)
+            })
+            .await
     }
 
     /// Advanced reasoning and problem-solving using Perplexity's sonar-reasoning-pro model.
@@ -375,17 +863,55 @@ impl PerplexityServer {
     pub async fn perplexity_reason(
         &self,
         Parameters(params): Parameters<PerplexityRequest>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
-        to_json_tool_result(
-            &self
-                .do_search(
-                    params,
-                    SearchMode::Reasoning,
-                    self.reason_model.map(ModelPreference::from),
-                    true,
+        self.metrics
+            .track("perplexity_reason", async {
+                to_json_tool_result(
+                    &self
+                        .do_search(
+                            &context,
+                            self.with_default_space(params),
+                            SearchMode::Reasoning,
+                            self.reason_models
+                                .iter()
+                                .copied()
+                                .map(ModelPreference::from)
+                                .collect(),
+                            true,
+                        )
+                        .await?,
                 )
-                .await?,
+            })
+            .await
+    }
+
+    /// Runs the same question against several models side by side.
+    #[tool(
+        name = "perplexity_compare",
+        description = "Ask several models the same question concurrently and compare their answers. \
+                Best for: checking how models differ on a question, cross-validating answers, \
+                and spotting sources only one model found. \
+                Returns each model's answer, sources and timing, plus the source URLs shared by all \
+                models and those unique to each. \
+                Requires authentication tokens.",
+        annotations(
+            title = "Compare Models",
+            read_only_hint = true,
+            open_world_hint = true,
+            destructive_hint = false,
+            idempotent_hint = false
         )
+    )]
+    pub async fn perplexity_compare(
+        &self,
+        Parameters(params): Parameters<PerplexityCompareRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        self.metrics
+            .track("perplexity_compare", async {
+                to_json_tool_result(&self.do_compare(params).await?)
+            })
+            .await
     }
 }
 
@@ -402,6 +928,7 @@ impl ServerHandler for PerplexityServer {
             instructions.push_str(
                 " Use perplexity_research for in-depth multi-source investigation (slow, 60s+). \
                 Use perplexity_reason for complex analysis requiring step-by-step logic. \
+                Use perplexity_compare to ask several models the same question side by side. \
                 All tools support an optional `files` parameter for document analysis: \
                 pass an array of objects each with `filename` and either `text` (plain-text content) \
                 or `data` (base64-encoded binary content, e.g. for PDFs).",
@@ -415,3 +942,75 @@ impl ServerHandler for PerplexityServer {
             .with_server_info(server_info)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::ErrorCode;
+
+    fn quota(research: u32, pro: u32) -> Quota {
+        Quota {
+            pro_remaining: Some(pro),
+            reasoning_remaining: None,
+            research_remaining: Some(research),
+        }
+    }
+
+    #[test]
+    fn researches_while_quota_remains_or_is_unknown() {
+        for quota in [None, Some(quota(3, 0)), Some(Quota::default())] {
+            let (mode, models, notice) =
+                plan_research(quota.as_ref(), false, &[SearchModel::Turbo]).unwrap();
+            assert_eq!(mode, SearchMode::DeepResearch);
+            assert!(models.is_empty());
+            assert!(notice.is_none());
+        }
+    }
+
+    #[test]
+    fn refuses_exhausted_research_without_downgrade() {
+        let error =
+            plan_research(Some(&quota(0, 5)), false, &[SearchModel::Turbo]).unwrap_err();
+        assert_eq!(error.code, ErrorCode::INVALID_REQUEST);
+        assert!(error.message.contains("perplexity_ask"));
+    }
+
+    #[test]
+    fn downgrades_exhausted_research_to_pro() {
+        let (mode, models, notice) =
+            plan_research(Some(&quota(0, 5)), true, &[SearchModel::Sonar]).unwrap();
+        assert_eq!(mode, SearchMode::Pro);
+        assert_eq!(models, vec![ModelPreference::from(SearchModel::Sonar)]);
+        assert!(notice.is_some());
+
+        let error =
+            plan_research(Some(&quota(0, 0)), true, &[SearchModel::Sonar]).unwrap_err();
+        assert!(error.message.contains("Pro quotas"));
+    }
+
+    #[test]
+    fn upload_progress_only_increases() {
+        let files = [
+            UploadFile::from_text("a.txt", "a".repeat(10)),
+            UploadFile::from_text("b.txt", "b".repeat(5)),
+        ];
+        let mut tracker = UploadProgressTracker::new(&files);
+        assert_eq!(tracker.total(), 17);
+
+        let mut update = |index, stage| {
+            tracker.update(&UploadProgress {
+                index,
+                filename: String::new(),
+                file_uuid: String::new(),
+                stage,
+            })
+        };
+        assert_eq!(update(0, UploadStage::Sending { sent: 10, total: 10 }), Some(10));
+        assert_eq!(update(0, UploadStage::Uploaded), None);
+        assert_eq!(update(0, UploadStage::Processing), None);
+        assert_eq!(update(0, UploadStage::Processed), Some(11));
+        // The second file comes from the attachment cache, without being sent.
+        assert_eq!(update(1, UploadStage::Processed), Some(17));
+        assert_eq!(update(1, UploadStage::Processed), None);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::disk_cache;
+use crate::error::{Error, Result};
+use crate::types::{AttachmentOutcome, AttachmentStatus, UploadFile};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncReadExt;
+
+/// Size of the chunks read from disk while hashing a file.
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Cache of uploaded attachments keyed by account, file contents and filename.
+///
+/// Files found in the cache are not uploaded again; their previous
+/// `s3_object_url` is reused until the entry is older than the TTL. Only
+/// files the server confirmed as processed are cached.
+/// Files streamed from readers are never cached, since hashing them would
+/// consume the reader. Clones share the same entries, and clients signed in
+/// to different accounts never see each other's uploads.
+#[derive(Debug, Clone)]
+pub struct AttachmentCache {
+    backend: Arc<Backend>,
+    ttl: Duration,
+    account: Arc<str>,
+}
+
+#[derive(Debug)]
+enum Backend {
+    Memory(Mutex<HashMap<String, CachedAttachment>>),
+    Disk(PathBuf),
+}
+
+/// A cached upload, stored as JSON by the disk backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedAttachment {
+    file_uuid: String,
+    s3_object_url: String,
+    page_count: Option<u64>,
+    char_count: Option<u64>,
+    truncated: bool,
+    stored_at: SystemTime,
+}
+
+impl AttachmentCache {
+    /// Creates a cache kept in memory for the lifetime of the client.
+    pub fn in_memory(ttl: Duration) -> Self {
+        Self { backend: Arc::new(Backend::Memory(Mutex::default())), ttl, account: "".into() }
+    }
+
+    /// Creates a cache stored as one JSON file per attachment in `dir`,
+    /// shared between processes and restarts.
+    ///
+    /// The directory is created on the first insert. Failures to read or
+    /// write it are treated as cache misses.
+    pub fn on_disk(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self { backend: Arc::new(Backend::Disk(dir.into())), ttl, account: "".into() }
+    }
+
+    /// Scopes the cache to the account identified by `account`.
+    pub(crate) fn for_account(mut self, account: &str) -> Self {
+        self.account = account.into();
+        self
+    }
+
+    /// Returns how long entries are reused.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Removes all entries. Other files in the cache directory are kept.
+    pub async fn clear(&self) -> std::io::Result<()> {
+        match &*self.backend {
+            Backend::Memory(entries) => {
+                entries.lock().unwrap_or_else(PoisonError::into_inner).clear();
+                Ok(())
+            }
+            Backend::Disk(dir) => disk_cache::remove_files(dir, None).await,
+        }
+    }
+
+    /// Returns the outcome of a previous upload stored under `key`, unless expired.
+    pub(crate) async fn get(&self, key: &str, filename: &str) -> Option<AttachmentOutcome> {
+        let cached = match &*self.backend {
+            Backend::Memory(entries) => {
+                entries.lock().unwrap_or_else(PoisonError::into_inner).get(key).cloned()?
+            }
+            Backend::Disk(dir) => {
+                let json = tokio::fs::read(dir.join(format!("{key}.json"))).await.ok()?;
+                serde_json::from_slice(&json).ok()?
+            }
+        };
+        if cached.stored_at.elapsed().is_ok_and(|age| age > self.ttl) {
+            self.remove(key).await;
+            return None;
+        }
+
+        Some(AttachmentOutcome {
+            filename: filename.to_string(),
+            file_uuid: cached.file_uuid,
+            s3_object_url: cached.s3_object_url,
+            status: AttachmentStatus::Processed,
+            page_count: cached.page_count,
+            char_count: cached.char_count,
+            truncated: cached.truncated,
+        })
+    }
+
+    /// Stores the outcome of an upload the server confirmed as processed.
+    ///
+    /// Rejected and unconfirmed files are not cached, so they are uploaded
+    /// again next time.
+    pub(crate) async fn insert(&self, key: String, outcome: &AttachmentOutcome) {
+        if outcome.status != AttachmentStatus::Processed {
+            return;
+        }
+        let cached = CachedAttachment {
+            file_uuid: outcome.file_uuid.clone(),
+            s3_object_url: outcome.s3_object_url.clone(),
+            page_count: outcome.page_count,
+            char_count: outcome.char_count,
+            truncated: outcome.truncated,
+            stored_at: SystemTime::now(),
+        };
+        match &*self.backend {
+            Backend::Memory(entries) => {
+                entries.lock().unwrap_or_else(PoisonError::into_inner).insert(key, cached);
+            }
+            Backend::Disk(dir) => {
+                let Ok(json) = serde_json::to_vec(&cached) else { return };
+                // Write to a temporary file first so readers never see a partial entry.
+                let tmp = dir.join(format!("{key}.{}.tmp", uuid::Uuid::new_v4()));
+                let stored = async {
+                    tokio::fs::create_dir_all(dir).await?;
+                    tokio::fs::write(&tmp, json).await?;
+                    tokio::fs::rename(&tmp, dir.join(format!("{key}.json"))).await
+                };
+                if stored.await.is_err() {
+                    let _ = tokio::fs::remove_file(&tmp).await;
+                }
+            }
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        match &*self.backend {
+            Backend::Memory(entries) => {
+                entries.lock().unwrap_or_else(PoisonError::into_inner).remove(key);
+            }
+            Backend::Disk(dir) => {
+                let _ = tokio::fs::remove_file(dir.join(format!("{key}.json"))).await;
+            }
+        }
+    }
+
+    /// Returns the cache key of a file: a SHA-256 over the account, and the
+    /// file's filename, content type, processing options and contents.
+    ///
+    /// Returns `None` for readers, which cannot be hashed without consuming
+    /// them.
+    pub(crate) async fn key(
+        &self,
+        file: &UploadFile,
+        content_type: &str,
+    ) -> Result<Option<String>> {
+        let options = file.options();
+        let mut hasher = Sha256::new();
+        for part in [
+            &*self.account,
+            file.filename(),
+            content_type,
+            options.source.as_deref().unwrap_or("default"),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update([
+            u8::from(options.force_image),
+            u8::from(options.skip_parsing),
+            u8::from(options.persistent_upload),
+        ]);
+        hasher.update(file.size().to_le_bytes());
+
+        match file {
+            UploadFile::Binary { data, .. } | UploadFile::Text { content: data, .. } => {
+                hasher.update(data);
+            }
+            UploadFile::Path { path, .. } => {
+                let mut source =
+                    tokio::fs::File::open(path).await.map_err(Error::UploadSource)?;
+                let mut buf = vec![0; HASH_CHUNK_SIZE];
+                loop {
+                    let read = source.read(&mut buf).await.map_err(Error::UploadSource)?;
+                    if read == 0 {
+                        break;
+                    }
+                    hasher.update(&buf[..read]);
+                }
+            }
+            UploadFile::Reader { .. } => return Ok(None),
+        }
+        Ok(Some(format!("{:x}", hasher.finalize())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(status: AttachmentStatus) -> AttachmentOutcome {
+        AttachmentOutcome {
+            filename: "notes.txt".to_string(),
+            file_uuid: "file-1".to_string(),
+            s3_object_url: "https://example.com/notes.txt".to_string(),
+            status,
+            page_count: Some(1),
+            char_count: None,
+            truncated: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn keys_depend_on_contents_and_filename() {
+        let cache = AttachmentCache::in_memory(Duration::from_secs(60));
+        let key = |file: UploadFile| {
+            let cache = cache.clone();
+            async move { cache.key(&file, "text/plain").await.unwrap() }
+        };
+        let original = key(UploadFile::from_text("notes.txt", "hello")).await;
+        assert!(original.is_some());
+        assert_eq!(original, key(UploadFile::from_bytes("notes.txt", "hello")).await);
+        assert_ne!(original, key(UploadFile::from_text("notes.txt", "hello!")).await);
+        assert_ne!(original, key(UploadFile::from_text("other.txt", "hello")).await);
+        assert_eq!(key(UploadFile::from_reader("notes.txt", &b"hello"[..], 5)).await, None);
+
+        let other_account = cache.clone().for_account("other");
+        let file = UploadFile::from_text("notes.txt", "hello");
+        assert_ne!(original, other_account.key(&file, "text/plain").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn returns_entries_until_expired() {
+        let cache = AttachmentCache::in_memory(Duration::from_secs(60));
+        cache.insert("key".to_string(), &outcome(AttachmentStatus::Processed)).await;
+        let hit = cache.get("key", "renamed.txt").await.unwrap();
+        assert_eq!(hit.filename, "renamed.txt");
+        assert_eq!(hit.status, AttachmentStatus::Processed);
+        assert_eq!(hit.s3_object_url, "https://example.com/notes.txt");
+
+        let expired = AttachmentCache::in_memory(Duration::ZERO);
+        expired.insert("key".to_string(), &outcome(AttachmentStatus::Processed)).await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(expired.get("key", "notes.txt").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn skips_files_not_processed() {
+        let cache = AttachmentCache::in_memory(Duration::from_secs(60));
+        let rejected = outcome(AttachmentStatus::Failed { reason: "corrupt".to_string() });
+        cache.insert("rejected".to_string(), &rejected).await;
+        assert!(cache.get("rejected", "notes.txt").await.is_none());
+
+        cache.insert("unconfirmed".to_string(), &outcome(AttachmentStatus::Unconfirmed)).await;
+        assert!(cache.get("unconfirmed", "notes.txt").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn persists_entries_on_disk() {
+        let dir =
+            std::env::temp_dir().join(format!("attachment-cache-{}", uuid::Uuid::new_v4()));
+        let cache = AttachmentCache::on_disk(&dir, Duration::from_secs(60));
+        let file = UploadFile::from_text("notes.txt", "hello");
+        let key = cache.key(&file, "text/plain").await.unwrap().unwrap();
+        cache.insert(key.clone(), &outcome(AttachmentStatus::Processed)).await;
+
+        let reopened = AttachmentCache::on_disk(&dir, Duration::from_secs(60));
+        let hit = reopened.get(&key, "notes.txt").await.unwrap();
+        assert_eq!(hit.file_uuid, "file-1");
+        assert_eq!(hit.status, AttachmentStatus::Processed);
+
+        reopened.clear().await.unwrap();
+        assert!(cache.get(&key, "notes.txt").await.is_none());
+        assert!(dir.is_dir());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+use sha2::{Digest, Sha256};
+
 /// Cookie name for the Perplexity session token.
 pub const SESSION_TOKEN_COOKIE_NAME: &str = "next-auth.session-token";
 /// Cookie name for the Perplexity CSRF token.
@@ -26,6 +28,12 @@ impl AuthCookies {
         &self.csrf_token
     }
 
+    /// Returns an identifier of the account, a SHA-256 of the session token,
+    /// so cached data of one account is never served to another.
+    pub(crate) fn account_id(&self) -> String {
+        format!("{:x}", Sha256::digest(self.session_token.as_bytes()))
+    }
+
     pub(crate) fn as_pairs(&self) -> [(&str, &str); 2] {
         [
             (SESSION_TOKEN_COOKIE_NAME, self.session_token()),
@@ -0,0 +1,59 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Spaces request starts evenly to stay within a requests-per-minute budget.
+#[derive(Debug)]
+pub(crate) struct RequestPacer {
+    interval: Option<Duration>,
+    next_start: Mutex<Option<Instant>>,
+}
+
+impl RequestPacer {
+    pub(crate) fn new(requests_per_minute: Option<u32>) -> Self {
+        let interval = requests_per_minute
+            .filter(|&requests| requests > 0)
+            .map(|requests| Duration::from_secs(60) / requests);
+        Self { interval, next_start: Mutex::new(None) }
+    }
+
+    /// Waits until the next request may start.
+    pub(crate) async fn wait(&self) {
+        if let Some(start) = self.reserve(Instant::now()) {
+            tokio::time::sleep_until(start).await;
+        }
+    }
+
+    /// Reserves the earliest free start at or after `now`.
+    fn reserve(&self, now: Instant) -> Option<Instant> {
+        let interval = self.interval?;
+        let mut next_start = self.next_start.lock().unwrap_or_else(PoisonError::into_inner);
+        let start = next_start.map_or(now, |next| next.max(now));
+        *next_start = Some(start + interval);
+        Some(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces_requests_within_budget() {
+        let pacer = RequestPacer::new(Some(30));
+        let now = Instant::now();
+
+        assert_eq!(pacer.reserve(now), Some(now));
+        assert_eq!(pacer.reserve(now), Some(now + Duration::from_secs(2)));
+        assert_eq!(pacer.reserve(now), Some(now + Duration::from_secs(4)));
+
+        let later = now + Duration::from_secs(60);
+        assert_eq!(pacer.reserve(later), Some(later));
+    }
+
+    #[test]
+    fn unlimited_pacer_never_waits() {
+        assert_eq!(RequestPacer::new(None).reserve(Instant::now()), None);
+        assert_eq!(RequestPacer::new(Some(0)).reserve(Instant::now()), None);
+    }
+}
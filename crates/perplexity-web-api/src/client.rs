@@ -1,20 +1,37 @@
+use crate::attachment_cache::AttachmentCache;
 use crate::auth::AuthCookies;
+use crate::batch::RequestPacer;
+use crate::compare::with_source_overlap;
 use crate::config::{
-    API_BASE_URL, API_MODE_CONCISE, API_MODE_COPILOT, API_VERSION, ENDPOINT_AUTH_SESSION,
-    ENDPOINT_SSE_ASK,
+    API_BASE_URL, API_MODE_CONCISE, API_MODE_COPILOT, API_REFERER, API_VERSION,
+    ENDPOINT_AUTH_SESSION, ENDPOINT_SSE_ASK,
 };
+use crate::conversation::{Conversation, ConversationState};
 use crate::error::{Error, Result};
-use crate::sse::SseStream;
+use crate::middleware::{Middleware, MiddlewareStack, RequestKind};
+use crate::models::{ModelPreference, try_model_chain};
+use crate::parse::is_final_event;
+use crate::quota::{self, UsageCounter};
+use crate::rate_limit::RateLimiter;
+use crate::response_cache::{CacheStats, ResponseCache};
+use crate::sse::{SseFrames, SseStream, parse_frame};
 use crate::types::{
-    AskParams, AskPayload, FollowUpContext, SearchEvent, SearchMode, SearchRequest,
-    SearchResponse, UploadFile,
+    AskParams, AskPayload, AttachmentOutcome, BatchLimits, Comparison, ComparisonEntry,
+    FollowUpContext, Pagination, Quota, ResponseMetadata, ResponseTimings, RetryPolicy,
+    SearchEvent, SearchMode, SearchRequest, SearchResponse, Space, SpaceFile, Thread,
+    ThreadSummary, UploadFile, UploadPolicy, UploadProgress, UploadProgressHandler, Usage,
 };
-use crate::upload::upload_files;
+use crate::upload::{accepted_urls, upload_files};
+use crate::{spaces, threads};
+use bytes::Bytes;
+use futures_util::future::join_all;
+use futures_util::stream;
 use futures_util::{Stream, StreamExt};
-use rquest::{Client as HttpClient, cookie::Jar};
+use rquest::header::REFERER;
+use rquest::{Client as HttpClient, Response, cookie::Jar};
 use rquest_util::Emulation;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 /// Default request timeout (30 seconds).
@@ -25,12 +42,30 @@ pub struct ClientBuilder {
     cookies: Option<AuthCookies>,
     http_client: Option<HttpClient>,
     timeout: Duration,
+    keep_raw: bool,
+    upload_policy: UploadPolicy,
+    attachment_cache: Option<AttachmentCache>,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    response_cache: Option<ResponseCache>,
+    middleware: Vec<Arc<dyn Middleware>>,
 }
 
 impl ClientBuilder {
     /// Creates a new builder with default settings.
     pub fn new() -> Self {
-        Self { cookies: None, http_client: None, timeout: DEFAULT_TIMEOUT }
+        Self {
+            cookies: None,
+            http_client: None,
+            timeout: DEFAULT_TIMEOUT,
+            keep_raw: true,
+            upload_policy: UploadPolicy::default(),
+            attachment_cache: None,
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+            response_cache: None,
+            middleware: Vec::new(),
+        }
     }
 
     /// Sets authentication cookies for the client.
@@ -51,19 +86,103 @@ impl ClientBuilder {
 
     /// Sets the request timeout.
     ///
-    /// Default is 30 seconds.
+    /// Files sent to storage may take longer: their upload only times out
+    /// once no data was sent for this long. Default is 30 seconds.
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout = timeout;
         self
     }
 
+    /// Sets whether events keep the full raw JSON value.
+    ///
+    /// When disabled, [`SearchEvent::raw`] and [`SearchResponse::raw`] are
+    /// [`serde_json::Value::Null`] and events are parsed without building
+    /// intermediate JSON values. Default is `true`.
+    pub fn keep_raw(mut self, keep_raw: bool) -> Self {
+        self.keep_raw = keep_raw;
+        self
+    }
+
+    /// Sets the limits checked before uploading files.
+    ///
+    /// Default is [`UploadPolicy::default`], which has no limits.
+    pub fn upload_policy(mut self, policy: UploadPolicy) -> Self {
+        self.upload_policy = policy;
+        self
+    }
+
+    /// Sets a cache of uploaded attachments.
+    ///
+    /// Files already uploaded within the cache's TTL are not uploaded again.
+    /// Entries are scoped to the account of the [`cookies`](Self::cookies),
+    /// so a cache shared by clients of different accounts stays separate.
+    /// Default is no cache.
+    pub fn attachment_cache(mut self, cache: AttachmentCache) -> Self {
+        self.attachment_cache = Some(cache);
+        self
+    }
+
+    /// Sets when and how often failed searches are retried.
+    ///
+    /// Applies to [`Client::search`] and [`Client::search_batch`]. Searches are
+    /// not retried by default.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Sets a rate limiter that every search request waits on.
+    ///
+    /// The limiter is shared by all clones of the client. Model fallbacks and
+    /// retries are requests of their own and wait as well.
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Sets a cache answering repeated searches without asking Perplexity again.
+    ///
+    /// Only [`Client::search`] and the calls built on it use the cache, and
+    /// requests with files, attachment URLs or follow-up context bypass it.
+    /// Cached answers still pass through the [`middleware`](Self::middleware),
+    /// which may veto them.
+    pub fn response_cache(mut self, cache: ResponseCache) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    /// Adds a middleware that sees every ask and upload request before it is
+    /// sent and every search event received.
+    ///
+    /// Middleware runs in the order it was added, and an error returned by any
+    /// of it fails the call without sending the request. A vetoed search does
+    /// not wait on the [`rate_limiter`](Self::rate_limiter). Searches answered
+    /// from the [`response_cache`](Self::response_cache) run the same hooks,
+    /// though their request is never sent.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
     /// Builds the client and performs initial session warm-up.
     ///
     /// This mirrors the Python client's behavior of making an initial
     /// GET request to `/api/auth/session` to establish a session.
     pub async fn build(self) -> Result<Client> {
-        let Self { cookies, http_client, timeout } = self;
+        let Self {
+            cookies,
+            http_client,
+            timeout,
+            keep_raw,
+            upload_policy,
+            attachment_cache,
+            retry_policy,
+            rate_limiter,
+            response_cache,
+            middleware,
+        } = self;
         let has_cookies = cookies.is_some();
+        let account = cookies.as_ref().map(AuthCookies::account_id).unwrap_or_default();
 
         let http = match http_client {
             Some(client) => client,
@@ -87,17 +206,92 @@ impl ClientBuilder {
             }
         };
 
-        let session_fut =
-            http.get(format!("{}{}", API_BASE_URL, ENDPOINT_AUTH_SESSION)).send();
-        tokio::time::timeout(timeout, session_fut)
-            .await
-            .map_err(|_| Error::Timeout(timeout))?
-            .map_err(Error::SessionWarmup)?;
+        warm_up(&http, timeout).await?;
+
+        Ok(Client {
+            http,
+            has_cookies,
+            timeout,
+            keep_raw,
+            upload_policy: Arc::new(upload_policy),
+            attachment_cache: attachment_cache.map(|cache| cache.for_account(&account)),
+            retry_policy,
+            rate_limiter,
+            response_cache: response_cache.map(|cache| cache.for_account(&account)),
+            usage: UsageCounter::default(),
+            middleware: MiddlewareStack::new(middleware),
+        })
+    }
+}
+
+/// Builds the request asking the query of `follow_up` again with `model`.
+fn rewrite_request(
+    follow_up: &FollowUpContext,
+    model: ModelPreference,
+) -> Result<SearchRequest> {
+    let query = match follow_up {
+        FollowUpContext { backend_uuid: Some(_), query: Some(query), .. } => query,
+        _ => return Err(Error::NothingToRewrite),
+    };
+    let mut request =
+        SearchRequest::new(query.as_str()).mode(model.search_mode()).model(model);
+    if !follow_up.sources.is_empty() {
+        request.sources.clone_from(&follow_up.sources);
+    }
+    if let Some(language) = &follow_up.language {
+        request.language.clone_from(language);
+    }
+    request.space.clone_from(&follow_up.space);
+    request.follow_up = Some(follow_up.clone());
+    Ok(request)
+}
 
-        Ok(Client { http, has_cookies, timeout })
+/// Builds the body of the ask request for `model`.
+fn ask_payload<'a>(
+    request: &'a SearchRequest,
+    attachments: &'a [String],
+    model: ModelPreference,
+) -> AskPayload<'a> {
+    let mode_str = match request.mode {
+        SearchMode::Auto => API_MODE_CONCISE,
+        SearchMode::Pro | SearchMode::Reasoning | SearchMode::DeepResearch => API_MODE_COPILOT,
+    };
+
+    let sources_str: Vec<&'static str> = request.sources.iter().map(|s| s.as_str()).collect();
+
+    AskPayload {
+        query_str: &request.query,
+        params: AskParams {
+            attachments,
+            frontend_context_uuid: Uuid::new_v4().to_string(),
+            frontend_uuid: Uuid::new_v4().to_string(),
+            is_incognito: request.incognito,
+            language: &request.language,
+            last_backend_uuid: request
+                .follow_up
+                .as_ref()
+                .and_then(|f| f.backend_uuid.as_deref()),
+            mode: mode_str,
+            model_preference: model.as_str(),
+            source: "default",
+            sources: sources_str,
+            target_collection_uuid: request.space.as_deref(),
+            version: API_VERSION,
+        },
     }
 }
 
+/// Establishes a session with a GET request to `/api/auth/session`.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+async fn warm_up(http: &HttpClient, timeout: Duration) -> Result<()> {
+    let session_fut = http.get(format!("{}{}", API_BASE_URL, ENDPOINT_AUTH_SESSION)).send();
+    tokio::time::timeout(timeout, session_fut)
+        .await
+        .map_err(|_| Error::Timeout(timeout))?
+        .map_err(Error::SessionWarmup)?;
+    Ok(())
+}
+
 impl Default for ClientBuilder {
     fn default() -> Self {
         Self::new()
@@ -129,6 +323,14 @@ pub struct Client {
     http: HttpClient,
     has_cookies: bool,
     timeout: Duration,
+    keep_raw: bool,
+    upload_policy: Arc<UploadPolicy>,
+    attachment_cache: Option<AttachmentCache>,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    response_cache: Option<ResponseCache>,
+    usage: UsageCounter,
+    middleware: MiddlewareStack,
 }
 
 impl Client {
@@ -137,84 +339,438 @@ impl Client {
         ClientBuilder::new()
     }
 
+    /// Returns the limits checked before uploading files.
+    pub fn upload_policy(&self) -> &UploadPolicy {
+        &self.upload_policy
+    }
+
     /// Performs a search query and returns the final response.
     ///
-    /// This method consumes the entire SSE stream and returns the final result.
+    /// This method reads the SSE stream until the server marks an event as
+    /// final (or the stream ends) and returns that result. Intermediate
+    /// snapshots are not fully parsed.
     /// For streaming responses, use [`search_stream`](Self::search_stream) instead.
+    ///
+    /// Failed searches are retried according to the client's [`RetryPolicy`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                mode = ?request.mode,
+                model = request.primary_model().as_str(),
+                sources = ?request.sources,
+                query_len = request.query.len(),
+                query = tracing::field::Empty,
+            ),
+        )
+    )]
     pub async fn search(&self, request: SearchRequest) -> Result<SearchResponse> {
-        let mut stream = Box::pin(self.search_stream(request).await?);
-        let mut last_event: Option<SearchEvent> = None;
+        #[cfg(feature = "tracing-queries")]
+        tracing::Span::current().record("query", request.query.as_str());
+        self.search_retrying(request, None).await
+    }
+
+    /// Runs many searches with bounded concurrency, yielding each result with
+    /// the index of its request as it completes.
+    ///
+    /// `limits` is either the maximum number of searches in flight or
+    /// [`BatchLimits`] that also cap the requests started per minute. Requests
+    /// are read from `requests` lazily, and failed searches are retried
+    /// according to the client's [`RetryPolicy`].
+    ///
+    /// ```no_run
+    /// use futures_util::StreamExt;
+    /// use perplexity_web_api::{BatchLimits, Client, SearchRequest};
+    ///
+    /// # async fn example(client: Client) {
+    /// let queries = ["What is Rust?", "What is Go?", "What is Zig?"];
+    /// let requests = queries.into_iter().map(SearchRequest::new);
+    ///
+    /// let mut results =
+    ///     Box::pin(client.search_batch(requests, BatchLimits::new(2).requests_per_minute(20)));
+    /// while let Some((index, result)) = results.next().await {
+    ///     match result {
+    ///         Ok(response) => println!("{}: {:?}", queries[index], response.answer),
+    ///         Err(e) => eprintln!("{}: {e}", queries[index]),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn search_batch<I, L>(
+        &self,
+        requests: I,
+        limits: L,
+    ) -> impl Stream<Item = (usize, Result<SearchResponse>)> + use<I, L>
+    where
+        I: IntoIterator<Item = SearchRequest>,
+        L: Into<BatchLimits>,
+    {
+        let limits = limits.into();
+        let client = self.clone();
+        let pacer = Arc::new(RequestPacer::new(limits.requests_per_minute));
+
+        stream::iter(requests.into_iter().enumerate())
+            .map(move |(index, request)| {
+                let client = client.clone();
+                let pacer = Arc::clone(&pacer);
+                async move { (index, client.search_retrying(request, Some(&pacer)).await) }
+            })
+            .buffer_unordered(limits.concurrency.max(1))
+    }
 
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(event) => last_event = Some(event),
+    /// Performs a search through the response cache, retrying failures
+    /// according to the retry policy.
+    ///
+    /// With a `pacer`, every attempt waits for its turn first.
+    async fn search_retrying(
+        &self,
+        request: SearchRequest,
+        pacer: Option<&RequestPacer>,
+    ) -> Result<SearchResponse> {
+        let cached = self
+            .response_cache
+            .as_ref()
+            .and_then(|cache| cache.key(&request).map(|key| (cache, key)));
+        if let Some((cache, key)) = &cached
+            && let Some(response) = cache.get(key).await
+        {
+            return self.serve_cached(&request, response);
+        }
+
+        let response = self.search_attempts(request, pacer).await?;
+        if let Some((cache, key)) = cached {
+            cache.insert(key, &response).await;
+        }
+        Ok(response)
+    }
+
+    /// Passes a cached response through the middleware as if it was asked
+    /// again: the ask request is built so middleware may veto it, but it is
+    /// not sent, and the response is seen as its final event.
+    fn serve_cached(
+        &self,
+        request: &SearchRequest,
+        response: SearchResponse,
+    ) -> Result<SearchResponse> {
+        if self.middleware.is_empty() {
+            return Ok(response);
+        }
+        let payload = ask_payload(request, &[], response.model_preference);
+        self.middleware.on_request(
+            RequestKind::Ask,
+            format!("{}{}", API_BASE_URL, ENDPOINT_SSE_ASK),
+            serde_json::to_value(&payload)?,
+        )?;
+        let metadata = &response.metadata;
+        self.middleware.on_event(&SearchEvent {
+            answer: response.answer.clone(),
+            web_results: response.web_results.clone(),
+            backend_uuid: response.follow_up.backend_uuid.clone(),
+            attachments: response.follow_up.attachments.clone(),
+            display_model: metadata.display_model.clone(),
+            mode: metadata.mode.clone(),
+            thread_url_slug: metadata.thread_url_slug.clone(),
+            status: metadata.status.clone(),
+            is_final: metadata.is_final,
+            frontend_uuid: metadata.frontend_uuid.clone(),
+            raw: response.raw.clone(),
+        })?;
+        Ok(response)
+    }
+
+    /// Performs a search, retrying failures according to the retry policy.
+    ///
+    /// Files are uploaded once, and only the ask request is retried with the
+    /// accepted attachments. Once a request is accepted the search is not
+    /// sent again, so a stream cut short does not spend another query.
+    async fn search_attempts(
+        &self,
+        request: SearchRequest,
+        pacer: Option<&RequestPacer>,
+    ) -> Result<SearchResponse> {
+        let attachments = self.prepare_attachments(&request).await?;
+        let mut retry = 0;
+        loop {
+            if let Some(pacer) = pacer {
+                pacer.wait().await;
+            }
+            let started = Instant::now();
+            match self.ask_with_fallback(&request, &attachments).await {
+                Err(e) if e.is_retryable() && retry < self.retry_policy.max_retries => {
+                    tokio::time::sleep(self.retry_policy.backoff(retry)).await;
+                    retry += 1;
+                }
                 Err(e) => return Err(e),
+                Ok((model_preference, response)) => {
+                    return self
+                        .read_response(request, model_preference, response, started)
+                        .await;
+                }
             }
         }
+    }
 
-        let SearchEvent { answer, web_results, backend_uuid, attachments, raw } =
-            last_event.ok_or(Error::UnexpectedEndOfStream)?;
-        let follow_up = FollowUpContext { backend_uuid, attachments };
+    /// Reads the events of an accepted search up to the final one.
+    async fn read_response(
+        &self,
+        request: SearchRequest,
+        model_preference: ModelPreference,
+        response: Response,
+        started: Instant,
+    ) -> Result<SearchResponse> {
+        let mut frames = Box::pin(SseFrames::new(response.bytes_stream()));
+        let mut last_frame: Option<Bytes> = None;
+        let mut time_to_first_event = None;
 
-        Ok(SearchResponse { answer, web_results, follow_up, raw })
+        while let Some(result) = frames.next().await {
+            let frame = result?;
+            time_to_first_event.get_or_insert_with(|| started.elapsed());
+            let is_final = is_final_event(&frame);
+            last_frame = Some(frame);
+            if is_final {
+                frames.as_mut().finish_at_final_event();
+                break;
+            }
+        }
+
+        let last_frame = last_frame.ok_or(Error::UnexpectedEndOfStream)?;
+        let SearchEvent {
+            answer,
+            web_results,
+            backend_uuid,
+            attachments,
+            display_model,
+            mode,
+            thread_url_slug,
+            status,
+            is_final,
+            frontend_uuid,
+            raw,
+        } = self.observe(parse_frame(&last_frame, self.keep_raw)?)?;
+        let metadata = ResponseMetadata {
+            display_model,
+            mode,
+            thread_url_slug,
+            status,
+            is_final,
+            frontend_uuid,
+            backend_uuid: backend_uuid.clone(),
+            timings: ResponseTimings { time_to_first_event, total: started.elapsed() },
+        };
+        let follow_up = FollowUpContext {
+            backend_uuid,
+            attachments,
+            query: Some(request.query),
+            sources: request.sources,
+            language: Some(request.language),
+            space: request.space,
+        };
+
+        Ok(SearchResponse { answer, web_results, follow_up, model_preference, metadata, raw })
     }
 
     /// Performs a search query and returns a stream of events.
     ///
     /// Each event contains partial or complete response data as it arrives.
     /// The stream ends when the server sends `event: end_of_stream`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                mode = ?request.mode,
+                model = request.primary_model().as_str(),
+                sources = ?request.sources,
+                query_len = request.query.len(),
+                query = tracing::field::Empty,
+            ),
+        )
+    )]
     pub async fn search_stream(
         &self,
         request: SearchRequest,
     ) -> Result<impl Stream<Item = Result<SearchEvent>>> {
+        #[cfg(feature = "tracing-queries")]
+        tracing::Span::current().record("query", request.query.as_str());
+        let (_, stream) = self.search_stream_with_model(request).await?;
+        Ok(stream)
+    }
+
+    /// Like [`search_stream`](Self::search_stream), also returning the model
+    /// preference that accepted the query.
+    pub(crate) async fn search_stream_with_model(
+        &self,
+        request: SearchRequest,
+    ) -> Result<(ModelPreference, impl Stream<Item = Result<SearchEvent>> + use<>)> {
+        let (model_preference, response) = self.send_with_fallback(request).await?;
+        let middleware = self.middleware.clone();
+        let events =
+            SseStream::new(response.bytes_stream(), self.keep_raw).map(move |event| {
+                let event = event?;
+                middleware.on_event(&event)?;
+                Ok(event)
+            });
+        Ok((model_preference, events))
+    }
+
+    /// Asks a previous query again with another model, streaming the new
+    /// answer.
+    ///
+    /// The query is sent as a follow-up of the previous turn, identified by
+    /// the `backend_uuid` of `follow_up`, with the sources, language and Space
+    /// of the original query. Both the backend UUID and the query are
+    /// required, as filled in by [`search`](Self::search) and
+    /// [`Conversation::follow_up`].
+    pub async fn rewrite(
+        &self,
+        follow_up: &FollowUpContext,
+        model: impl Into<ModelPreference>,
+    ) -> Result<impl Stream<Item = Result<SearchEvent>>> {
+        self.search_stream(rewrite_request(follow_up, model.into())?).await
+    }
+
+    /// Runs the same query against several models concurrently.
+    ///
+    /// Each model is queried in the search mode that serves it, and fallback
+    /// models of `request` are ignored. Files are uploaded once and
+    /// attached to every query. A model failing does not fail the comparison,
+    /// its entry holds the error instead.
+    pub async fn compare(
+        &self,
+        mut request: SearchRequest,
+        models: &[ModelPreference],
+    ) -> Result<Comparison> {
+        if models.is_empty() {
+            return Err(Error::NoModelsToCompare);
+        }
         self.validate_request(&request)?;
 
+        if !request.files.is_empty() {
+            let files = std::mem::take(&mut request.files);
+            let file_refs: Vec<&UploadFile> = files.iter().collect();
+            let outcomes = upload_files(
+                &self.http,
+                &file_refs,
+                self.timeout,
+                &self.upload_policy,
+                self.attachment_cache.as_ref(),
+                request.upload_progress.as_ref(),
+                &self.middleware,
+            )
+            .await?;
+            request.attachment_urls.extend(accepted_urls(outcomes)?);
+        }
+        request.fallback_models.clear();
+
+        let searches = models.iter().map(|&model| {
+            let request = request.clone().mode(model.search_mode()).model(model);
+            async move {
+                let started = Instant::now();
+                let result = self.search(request).await;
+                ComparisonEntry {
+                    model,
+                    result,
+                    unique_urls: Vec::new(),
+                    elapsed: started.elapsed(),
+                }
+            }
+        });
+
+        Ok(with_source_overlap(join_all(searches).await))
+    }
+
+    /// Starts a new [`Conversation`] that carries follow-up context between
+    /// queries automatically.
+    pub fn conversation(&self) -> Conversation {
+        Conversation::new(self.clone(), ConversationState::default())
+    }
+
+    /// Resumes a conversation from a previously saved [`ConversationState`].
+    pub fn resume_conversation(&self, state: ConversationState) -> Conversation {
+        Conversation::new(self.clone(), state)
+    }
+
+    /// Uploads the request files and sends the ask request, walking the model
+    /// chain until one of the models accepts the query.
+    ///
+    /// Returns the model preference that was accepted along with the response.
+    async fn send_with_fallback(
+        &self,
+        request: SearchRequest,
+    ) -> Result<(ModelPreference, Response)> {
+        let attachments = self.prepare_attachments(&request).await?;
+        self.ask_with_fallback(&request, &attachments).await
+    }
+
+    /// Uploads the request files and returns the URLs to attach to the ask
+    /// request, including those of the request and its follow-up context.
+    async fn prepare_attachments(&self, request: &SearchRequest) -> Result<Vec<String>> {
+        self.validate_request(request)?;
+
         let file_refs: Vec<&UploadFile> = request.files.iter().collect();
-        let mut attachments = upload_files(&self.http, &file_refs, self.timeout).await?;
+        let outcomes = upload_files(
+            &self.http,
+            &file_refs,
+            self.timeout,
+            &self.upload_policy,
+            self.attachment_cache.as_ref(),
+            request.upload_progress.as_ref(),
+            &self.middleware,
+        )
+        .await?;
+        let mut attachments = accepted_urls(outcomes)?;
+        attachments.extend(request.attachment_urls.iter().cloned());
 
         if let Some(ref follow_up) = request.follow_up {
             attachments.extend(follow_up.attachments.clone());
         }
+        Ok(attachments)
+    }
 
-        let mode_str = match request.mode {
-            SearchMode::Auto => API_MODE_CONCISE,
-            SearchMode::Pro | SearchMode::Reasoning | SearchMode::DeepResearch => {
-                API_MODE_COPILOT
-            }
-        };
+    /// Sends the ask request, walking the model chain until one of the models
+    /// accepts the query.
+    async fn ask_with_fallback(
+        &self,
+        request: &SearchRequest,
+        attachments: &[String],
+    ) -> Result<(ModelPreference, Response)> {
+        let accepted =
+            try_model_chain(request.primary_model(), &request.fallback_models, |model| {
+                self.send_ask(request, attachments, model)
+            })
+            .await?;
+        self.usage.record(request.mode);
+        Ok(accepted)
+    }
 
-        let model_pref = request
-            .model_preference
-            .map(|preference| preference.as_str())
-            .unwrap_or_else(|| request.mode.default_preference());
-
-        let sources_str: Vec<&'static str> =
-            request.sources.iter().map(|s| s.as_str()).collect();
-
-        let payload = AskPayload {
-            query_str: &request.query,
-            params: AskParams {
-                attachments,
-                frontend_context_uuid: Uuid::new_v4().to_string(),
-                frontend_uuid: Uuid::new_v4().to_string(),
-                is_incognito: request.incognito,
-                language: &request.language,
-                last_backend_uuid: request.follow_up.and_then(|f| f.backend_uuid),
-                mode: mode_str,
-                model_preference: model_pref,
-                source: "default",
-                sources: sources_str,
-                version: API_VERSION,
-            },
-        };
+    /// Sends the ask request for a single model preference.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(model = model.as_str()))
+    )]
+    async fn send_ask(
+        &self,
+        request: &SearchRequest,
+        attachments: &[String],
+        model: ModelPreference,
+    ) -> Result<Response> {
+        let payload = ask_payload(request, attachments, model);
+        let outgoing = self.middleware.on_request(
+            RequestKind::Ask,
+            format!("{}{}", API_BASE_URL, ENDPOINT_SSE_ASK),
+            serde_json::to_value(&payload)?,
+        )?;
 
-        let request_fut = self
-            .http
-            .post(format!("{}{}", API_BASE_URL, ENDPOINT_SSE_ASK))
-            .json(&payload)
-            .send();
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire(request.mode).await?;
+        }
 
-        let response = tokio::time::timeout(self.timeout, request_fut)
+        let request_fut = outgoing.build(&self.http).send();
+
+        tokio::time::timeout(self.timeout, request_fut)
             .await
             .map_err(|_| Error::Timeout(self.timeout))?
             .map_err(Error::SearchRequest)?
@@ -222,28 +778,304 @@ impl Client {
             .map_err(|e| Error::Server {
                 status: e.status().map(|s| s.as_u16()).unwrap_or(0),
                 message: e.to_string(),
-            })?;
+            })
+    }
 
-        Ok(SseStream::new(response.bytes_stream()))
+    /// Passes a parsed event to the middleware.
+    fn observe(&self, event: SearchEvent) -> Result<SearchEvent> {
+        self.middleware.on_event(&event)?;
+        Ok(event)
     }
 
     /// Uploads multiple files in a single batch and returns their S3 object URLs.
     ///
+    /// The files are checked against the client's [`UploadPolicy`] first, and
+    /// files found in its [`AttachmentCache`] are not uploaded again.
     /// All files are registered with the backend in one request, then uploaded
-    /// to S3 in parallel, and finally processed server-side.
+    /// to S3 in parallel, and finally processed server-side. Fails with
+    /// [`Error::AttachmentRejected`] if the server rejects any of the files,
+    /// or [`Error::AttachmentUnconfirmed`] if it does not report on one.
     /// Requires authentication cookies.
+    ///
+    /// The returned URLs can be attached to any number of later searches with
+    /// [`SearchRequest::attachment_url`], so a corpus is uploaded only once.
     pub async fn upload_files(&self, files: &[&UploadFile]) -> Result<Vec<String>> {
         if !files.is_empty() && !self.has_cookies {
             return Err(Error::FileUploadRequiresAuth);
         }
-        upload_files(&self.http, files, self.timeout).await
+        accepted_urls(
+            upload_files(
+                &self.http,
+                files,
+                self.timeout,
+                &self.upload_policy,
+                self.attachment_cache.as_ref(),
+                None,
+                &self.middleware,
+            )
+            .await?,
+        )
+    }
+
+    /// Uploads multiple files like [`upload_files`](Self::upload_files) and
+    /// returns the processing outcome of every file.
+    ///
+    /// Files rejected by the server are reported as
+    /// [`AttachmentStatus::Failed`](crate::AttachmentStatus::Failed)
+    /// instead of failing the whole batch.
+    pub async fn upload_files_with_outcomes(
+        &self,
+        files: &[&UploadFile],
+    ) -> Result<Vec<AttachmentOutcome>> {
+        if !files.is_empty() && !self.has_cookies {
+            return Err(Error::FileUploadRequiresAuth);
+        }
+        upload_files(
+            &self.http,
+            files,
+            self.timeout,
+            &self.upload_policy,
+            self.attachment_cache.as_ref(),
+            None,
+            &self.middleware,
+        )
+        .await
+    }
+
+    /// Uploads multiple files like [`upload_files`](Self::upload_files),
+    /// reporting the progress of every file to `on_progress`.
+    pub async fn upload_files_with_progress(
+        &self,
+        files: &[&UploadFile],
+        on_progress: impl Fn(UploadProgress) + Send + Sync + 'static,
+    ) -> Result<Vec<String>> {
+        if !files.is_empty() && !self.has_cookies {
+            return Err(Error::FileUploadRequiresAuth);
+        }
+        let handler = UploadProgressHandler::new(on_progress);
+        accepted_urls(
+            upload_files(
+                &self.http,
+                files,
+                self.timeout,
+                &self.upload_policy,
+                self.attachment_cache.as_ref(),
+                Some(&handler),
+                &self.middleware,
+            )
+            .await?,
+        )
+    }
+
+    /// Downloads the contents of an attachment by its S3 object URL, e.g. one
+    /// of [`SearchEvent::attachments`] or a URL returned by
+    /// [`upload_files`](Self::upload_files).
+    ///
+    /// The request is sent through the client's session, with its cookies
+    /// and browser headers, and the body is streamed as it arrives.
+    pub async fn download_attachment(
+        &self,
+        url: &str,
+    ) -> Result<impl Stream<Item = Result<Bytes>> + use<>> {
+        let request_fut = self.http.get(url).header(REFERER, API_REFERER).send();
+
+        let response = tokio::time::timeout(self.timeout, request_fut)
+            .await
+            .map_err(|_| Error::Timeout(self.timeout))?
+            .map_err(Error::AttachmentDownload)?
+            .error_for_status()
+            .map_err(Error::AttachmentDownload)?;
+
+        Ok(response.bytes_stream().map(|chunk| chunk.map_err(Error::AttachmentDownload)))
+    }
+
+    /// Lists threads of the user's library, newest first.
+    ///
+    /// Threads are created by queries sent without incognito mode.
+    /// Requires authentication cookies.
+    pub async fn list_threads(&self, page: Pagination) -> Result<Vec<ThreadSummary>> {
+        self.require_cookies(Error::ThreadsRequireAuth)?;
+        threads::list_threads(&self.http, self.timeout, page).await
+    }
+
+    /// Fetches a thread by its slug or UUID with every query, answer and
+    /// source. Requires authentication cookies.
+    pub async fn get_thread(&self, slug_or_uuid: &str) -> Result<Thread> {
+        self.require_cookies(Error::ThreadsRequireAuth)?;
+        threads::get_thread(&self.http, self.timeout, slug_or_uuid).await
+    }
+
+    /// Deletes a thread by its slug or UUID from the user's library.
+    /// Requires authentication cookies.
+    pub async fn delete_thread(&self, slug_or_uuid: &str) -> Result<()> {
+        self.require_cookies(Error::ThreadsRequireAuth)?;
+        threads::delete_thread(&self.http, self.timeout, slug_or_uuid).await
+    }
+
+    /// Lists the user's Spaces. Requires authentication cookies.
+    pub async fn list_spaces(&self, page: Pagination) -> Result<Vec<Space>> {
+        self.require_cookies(Error::SpacesRequireAuth)?;
+        spaces::list_spaces(&self.http, self.timeout, page).await
+    }
+
+    /// Fetches a Space by its slug or UUID, including its instructions.
+    /// Requires authentication cookies.
+    pub async fn get_space(&self, slug_or_uuid: &str) -> Result<Space> {
+        self.require_cookies(Error::SpacesRequireAuth)?;
+        spaces::get_space(&self.http, self.timeout, slug_or_uuid).await
+    }
+
+    /// Lists the files attached to the Space with the given UUID.
+    /// Requires authentication cookies.
+    pub async fn list_space_files(
+        &self,
+        uuid: &str,
+        page: Pagination,
+    ) -> Result<Vec<SpaceFile>> {
+        self.require_cookies(Error::SpacesRequireAuth)?;
+        spaces::list_space_files(&self.http, self.timeout, uuid, page).await
+    }
+
+    /// Fetches the remaining Pro, reasoning and research allowances of the
+    /// account.
+    ///
+    /// Requires authentication cookies.
+    pub async fn quota(&self) -> Result<Quota> {
+        self.require_cookies(Error::QuotaRequiresAuth)?;
+        quota::fetch_quota(&self.http, self.timeout).await
+    }
+
+    /// Returns the hit and miss counts of the response cache, if one is set.
+    pub fn response_cache_stats(&self) -> Option<CacheStats> {
+        self.response_cache.as_ref().map(ResponseCache::stats)
+    }
+
+    /// Returns the searches sent per mode by this client and its clones.
+    ///
+    /// Every request accepted by the server counts, including model fallbacks
+    /// and retries.
+    pub fn usage(&self) -> Usage {
+        self.usage.snapshot()
+    }
+
+    /// Fails with `error` unless the client has authentication cookies.
+    fn require_cookies(&self, error: Error) -> Result<()> {
+        if self.has_cookies { Ok(()) } else { Err(error) }
     }
 
     fn validate_request(&self, request: &SearchRequest) -> Result<()> {
-        if !request.files.is_empty() && !self.has_cookies {
+        let has_attachments = !request.files.is_empty() || !request.attachment_urls.is_empty();
+        if has_attachments && !self.has_cookies {
             return Err(Error::FileUploadRequiresAuth);
         }
+        if request.space.is_some() {
+            self.require_cookies(Error::SpacesRequireAuth)?;
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{Middleware, OutgoingRequest};
+    use crate::models::SearchModel;
+    use crate::types::Source;
+    use std::sync::Mutex;
+
+    /// Records the answers it sees and vetoes queries containing "secret".
+    struct Audit(Arc<Mutex<Vec<String>>>);
+
+    impl Middleware for Audit {
+        fn on_request(&self, request: &mut OutgoingRequest) -> Result<()> {
+            if request.body["query_str"].as_str().unwrap_or_default().contains("secret") {
+                return Err(Error::Rejected("secret query".to_string()));
+            }
+            Ok(())
+        }
+
+        fn on_event(&self, event: &SearchEvent) -> Result<()> {
+            self.0.lock().unwrap().extend(event.answer.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_answers_pass_through_middleware() {
+        let cache = ResponseCache::in_memory(4, Duration::from_secs(60));
+        let answers = Arc::new(Mutex::new(Vec::new()));
+        let client = Client {
+            http: HttpClient::builder().build().unwrap(),
+            has_cookies: false,
+            timeout: Duration::from_secs(1),
+            keep_raw: false,
+            upload_policy: Arc::default(),
+            attachment_cache: None,
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+            response_cache: Some(cache.clone()),
+            usage: UsageCounter::default(),
+            middleware: MiddlewareStack::new(vec![Arc::new(Audit(Arc::clone(&answers)))]),
+        };
+        for query in ["public", "secret"] {
+            let response = SearchResponse {
+                answer: Some(format!("{query} answer")),
+                web_results: Vec::new(),
+                follow_up: FollowUpContext::default(),
+                model_preference: SearchModel::Turbo.into(),
+                metadata: ResponseMetadata::default(),
+                raw: serde_json::Value::Null,
+            };
+            let key = cache.key(&SearchRequest::new(query)).unwrap();
+            cache.insert(key, &response).await;
+        }
+
+        let response = client.search(SearchRequest::new("public")).await.unwrap();
+        assert_eq!(response.answer.as_deref(), Some("public answer"));
+        assert!(matches!(
+            client.search(SearchRequest::new("secret")).await,
+            Err(Error::Rejected(_))
+        ));
+        assert_eq!(*answers.lock().unwrap(), vec!["public answer".to_string()]);
+        assert_eq!(cache.stats().hits, 2);
+    }
+
+    #[test]
+    fn rewrite_keeps_options_of_the_original_query() {
+        let follow_up = FollowUpContext {
+            backend_uuid: Some("backend-1".to_string()),
+            attachments: vec!["https://s3.example/a.pdf".to_string()],
+            query: Some("Explain borrow checking".to_string()),
+            sources: vec![Source::Scholar],
+            language: Some("de-DE".to_string()),
+            space: Some("space-1".to_string()),
+        };
+        let model = ModelPreference::from(SearchModel::Claude46Sonnet);
+        let request = rewrite_request(&follow_up, model).unwrap();
+        assert_eq!(request.primary_model(), model);
+        let attachments = follow_up.attachments.clone();
+        let payload =
+            serde_json::to_value(ask_payload(&request, &attachments, request.primary_model()))
+                .unwrap();
+
+        assert_eq!(payload["query_str"], "Explain borrow checking");
+        let params = &payload["params"];
+        assert_eq!(params["sources"], serde_json::json!(["scholar"]));
+        assert_eq!(params["language"], "de-DE");
+        assert_eq!(params["target_collection_uuid"], "space-1");
+        assert_eq!(params["last_backend_uuid"], "backend-1");
+        assert_eq!(params["attachments"][0], "https://s3.example/a.pdf");
+        assert_eq!(params["model_preference"], request.primary_model().as_str());
+        assert!(params.get("query_source").is_none());
+    }
+
+    #[test]
+    fn rewrite_requires_backend_uuid_and_query() {
+        let follow_up = FollowUpContext { query: Some("q".to_string()), ..Default::default() };
+        assert!(matches!(
+            rewrite_request(&follow_up, SearchModel::Turbo.into()),
+            Err(Error::NothingToRewrite)
+        ));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::types::{Comparison, ComparisonEntry};
+use std::collections::HashSet;
+
+/// Fills in the source overlap between the models that answered.
+pub(crate) fn with_source_overlap(mut entries: Vec<ComparisonEntry>) -> Comparison {
+    let urls: Vec<Option<Vec<&str>>> = entries
+        .iter()
+        .map(|entry| {
+            entry.result.as_ref().ok().map(|response| {
+                let mut seen = HashSet::new();
+                response
+                    .web_results
+                    .iter()
+                    .map(|result| result.url.as_str())
+                    .filter(|url| seen.insert(*url))
+                    .collect()
+            })
+        })
+        .collect();
+    let answered: Vec<&Vec<&str>> = urls.iter().flatten().collect();
+
+    // A URL is only shared when at least two models answered and cited it.
+    let shared_urls: Vec<String> = match answered.split_first() {
+        Some((first, rest)) if !rest.is_empty() => first
+            .iter()
+            .filter(|url| rest.iter().all(|other| other.contains(url)))
+            .map(|url| (*url).to_string())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let unique_urls: Vec<Vec<String>> = urls
+        .iter()
+        .enumerate()
+        .map(|(index, own)| {
+            let Some(own) = own else { return Vec::new() };
+            own.iter()
+                .filter(|url| {
+                    urls.iter().enumerate().filter(|(other, _)| *other != index).all(
+                        |(_, other)| other.as_ref().is_none_or(|other| !other.contains(url)),
+                    )
+                })
+                .map(|url| (*url).to_string())
+                .collect()
+        })
+        .collect();
+
+    for (entry, unique) in entries.iter_mut().zip(unique_urls) {
+        entry.unique_urls = unique;
+    }
+    Comparison { entries, shared_urls }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::types::{FollowUpContext, ResponseMetadata, SearchResponse, SearchWebResult};
+    use crate::{ModelPreference, SearchModel};
+    use std::time::Duration;
+
+    fn entry(model: SearchModel, urls: &[&str]) -> ComparisonEntry {
+        let model: ModelPreference = model.into();
+        let web_results = urls
+            .iter()
+            .map(|url| SearchWebResult {
+                name: String::new(),
+                url: (*url).to_string(),
+                snippet: String::new(),
+            })
+            .collect();
+        let response = SearchResponse {
+            answer: Some("answer".to_string()),
+            web_results,
+            follow_up: FollowUpContext::default(),
+            model_preference: model,
+            metadata: ResponseMetadata::default(),
+            raw: serde_json::Value::Null,
+        };
+        ComparisonEntry {
+            model,
+            result: Ok(response),
+            unique_urls: Vec::new(),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn splits_shared_and_unique_urls() {
+        let comparison = with_source_overlap(vec![
+            entry(SearchModel::Gpt54, &["https://a", "https://b", "https://a"]),
+            entry(SearchModel::Claude46Sonnet, &["https://b", "https://c"]),
+        ]);
+
+        assert_eq!(comparison.shared_urls, ["https://b"]);
+        assert_eq!(comparison.entries[0].unique_urls, ["https://a"]);
+        assert_eq!(comparison.entries[1].unique_urls, ["https://c"]);
+    }
+
+    #[test]
+    fn single_answer_shares_nothing() {
+        let mut failed = entry(SearchModel::Sonar, &[]);
+        failed.result = Err(Error::UnexpectedEndOfStream);
+        let comparison =
+            with_source_overlap(vec![entry(SearchModel::Gpt54, &["https://a"]), failed]);
+
+        assert!(comparison.shared_urls.is_empty());
+        assert_eq!(comparison.entries[0].unique_urls, ["https://a"]);
+    }
+
+    #[test]
+    fn ignores_failed_models() {
+        let mut failed = entry(SearchModel::Sonar, &[]);
+        failed.result = Err(Error::UnexpectedEndOfStream);
+        let comparison = with_source_overlap(vec![
+            entry(SearchModel::Gpt54, &["https://a", "https://b"]),
+            failed,
+            entry(SearchModel::Claude46Sonnet, &["https://a"]),
+        ]);
+
+        assert_eq!(comparison.shared_urls, ["https://a"]);
+        assert_eq!(comparison.entries[0].unique_urls, ["https://b"]);
+        assert!(comparison.entries[1].unique_urls.is_empty());
+        assert_eq!(comparison.successful().count(), 2);
+    }
+}
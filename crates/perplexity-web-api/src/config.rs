@@ -6,6 +6,14 @@ pub const ENDPOINT_AUTH_SESSION: &str = "/api/auth/session";
 pub const ENDPOINT_SSE_ASK: &str = "/rest/sse/perplexity_ask";
 pub const ENDPOINT_BATCH_UPLOAD_URL: &str = "/rest/uploads/batch_create_upload_urls";
 pub const ENDPOINT_ATTACHMENT_PROCESSING: &str = "/rest/sse/attachment_processing/subscribe";
+pub const ENDPOINT_THREAD_LIST: &str = "/rest/thread/list_ask_threads";
+pub const ENDPOINT_THREAD: &str = "/rest/thread";
+pub const ENDPOINT_THREAD_DELETE: &str = "/rest/thread/delete_thread_by_entry_uuid";
+pub const ENDPOINT_SPACE_LIST: &str = "/rest/collections/list_user_collections";
+pub const ENDPOINT_SPACE: &str = "/rest/collections/get_collection";
+pub const ENDPOINT_SPACE_FILES: &str = "/rest/file-repository/list-files";
+pub const ENDPOINT_USER_SETTINGS: &str = "/rest/user/settings";
+pub const THREAD_PATH: &str = "/search";
 
 pub const API_MODE_CONCISE: &str = "concise";
 pub const API_MODE_COPILOT: &str = "copilot";
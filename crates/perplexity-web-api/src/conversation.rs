@@ -0,0 +1,399 @@
+use crate::client::Client;
+use crate::error::Result;
+use crate::models::ModelPreference;
+use crate::types::{
+    FollowUpContext, SearchEvent, SearchMode, SearchRequest, SearchResponse, SearchWebResult,
+    Source,
+};
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+/// Saved state of a [`Conversation`]: its query defaults, follow-up context
+/// and history.
+///
+/// Serialize it to persist a conversation and resume it later with
+/// [`Client::resume_conversation`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversationState {
+    /// Search mode of every query.
+    pub mode: SearchMode,
+    /// Model chain of every query; the mode's default model when empty.
+    pub models: Vec<ModelPreference>,
+    /// Information sources of every query.
+    pub sources: Vec<Source>,
+    /// Language code (ISO 639) of every query.
+    pub language: String,
+    /// Whether queries use incognito mode.
+    pub incognito: bool,
+    /// UUID of the Space every query searches within.
+    pub space: Option<String>,
+    /// Backend UUID of the last answer, which the next query follows up on.
+    pub backend_uuid: Option<String>,
+    /// Attachment URLs carried into every following query.
+    pub attachments: Vec<String>,
+    /// Queries asked so far with their answers, oldest first.
+    pub turns: Vec<ConversationTurn>,
+}
+
+impl Default for ConversationState {
+    fn default() -> Self {
+        Self {
+            mode: SearchMode::Auto,
+            models: Vec::new(),
+            sources: vec![Source::Web],
+            language: "en-US".to_string(),
+            incognito: false,
+            space: None,
+            backend_uuid: None,
+            attachments: Vec::new(),
+            turns: Vec::new(),
+        }
+    }
+}
+
+impl ConversationState {
+    /// Returns the context the next query follows up on.
+    fn follow_up(&self) -> FollowUpContext {
+        FollowUpContext {
+            backend_uuid: self.backend_uuid.clone(),
+            attachments: self.attachments.clone(),
+            query: self.turns.last().map(|turn| turn.query.clone()),
+            sources: self.sources.clone(),
+            language: Some(self.language.clone()),
+            space: self.space.clone(),
+        }
+    }
+
+    /// Builds a request for `query` with the defaults and follow-up context.
+    fn request(&self, query: impl Into<String>) -> SearchRequest {
+        let mut request = SearchRequest::new(query)
+            .mode(self.mode)
+            .sources(self.sources.clone())
+            .language(self.language.clone())
+            .incognito(self.incognito);
+        if !self.models.is_empty() {
+            request = request.models(self.models.iter().copied());
+        }
+        if let Some(space) = &self.space {
+            request = request.space(space.clone());
+        }
+        if self.backend_uuid.is_some() || !self.attachments.is_empty() {
+            request = request.follow_up(self.follow_up());
+        }
+        request
+    }
+
+    /// Records a query the server accepted, before its answer arrives.
+    fn start_turn(
+        &mut self,
+        query: String,
+        model_preference: ModelPreference,
+        attachment_urls: Vec<String>,
+    ) {
+        self.add_attachments(attachment_urls);
+        self.turns.push(ConversationTurn {
+            query,
+            answer: None,
+            web_results: Vec::new(),
+            model_preference,
+            backend_uuid: None,
+        });
+    }
+
+    /// Updates the current turn and the follow-up context from an event.
+    fn record_event(&mut self, event: &SearchEvent) {
+        if let Some(backend_uuid) = &event.backend_uuid {
+            self.backend_uuid = Some(backend_uuid.clone());
+        }
+        self.add_attachments(event.attachments.iter().cloned());
+
+        let Some(turn) = self.turns.last_mut() else { return };
+        if event.answer.is_some() {
+            turn.answer.clone_from(&event.answer);
+        }
+        if !event.web_results.is_empty() {
+            turn.web_results.clone_from(&event.web_results);
+        }
+        if event.backend_uuid.is_some() {
+            turn.backend_uuid.clone_from(&event.backend_uuid);
+        }
+    }
+
+    fn add_attachments(&mut self, urls: impl IntoIterator<Item = String>) {
+        for url in urls {
+            if !self.attachments.contains(&url) {
+                self.attachments.push(url);
+            }
+        }
+    }
+}
+
+/// A query and its answer within a [`Conversation`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationTurn {
+    /// The query string.
+    pub query: String,
+    /// The answer text, if any was received.
+    pub answer: Option<String>,
+    /// Web search results of the answer.
+    pub web_results: Vec<SearchWebResult>,
+    /// Model preference that accepted the query.
+    pub model_preference: ModelPreference,
+    /// Backend UUID of the answer.
+    pub backend_uuid: Option<String>,
+}
+
+/// A thread of queries that follow up on each other.
+///
+/// Created with [`Client::conversation`]. Every query continues from the
+/// previous answer and carries the attachments seen so far, so follow-up
+/// context never has to be copied by hand.
+///
+/// ```no_run
+/// # async fn example(client: perplexity_web_api::Client) -> perplexity_web_api::Result<()> {
+/// let mut conversation = client.conversation();
+/// conversation.ask("Who designed Rust?").await?;
+/// let response = conversation.ask("When was its first stable release?").await?;
+/// println!("{}", response.answer.unwrap_or_default());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Conversation {
+    client: Client,
+    state: ConversationState,
+}
+
+impl Conversation {
+    pub(crate) fn new(client: Client, state: ConversationState) -> Self {
+        Self { client, state }
+    }
+
+    /// Sets the search mode of following queries.
+    pub fn mode(mut self, mode: SearchMode) -> Self {
+        self.state.mode = mode;
+        self
+    }
+
+    /// Sets the model of following queries.
+    pub fn model(mut self, model: impl Into<ModelPreference>) -> Self {
+        self.state.models = vec![model.into()];
+        self
+    }
+
+    /// Sets the model chain of following queries; see [`SearchRequest::models`].
+    pub fn models<I, M>(mut self, models: I) -> Self
+    where
+        I: IntoIterator<Item = M>,
+        M: Into<ModelPreference>,
+    {
+        self.state.models = models.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the information sources of following queries.
+    pub fn sources(mut self, sources: Vec<Source>) -> Self {
+        self.state.sources = sources;
+        self
+    }
+
+    /// Sets the language of following queries.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.state.language = language.into();
+        self
+    }
+
+    /// Enables or disables incognito mode for following queries.
+    pub fn incognito(mut self, incognito: bool) -> Self {
+        self.state.incognito = incognito;
+        self
+    }
+
+    /// Sets the Space following queries search within.
+    pub fn space(mut self, space: impl Into<String>) -> Self {
+        self.state.space = Some(space.into());
+        self
+    }
+
+    /// Returns the state of the conversation.
+    pub fn state(&self) -> &ConversationState {
+        &self.state
+    }
+
+    /// Returns the state of the conversation, e.g. to persist it.
+    pub fn into_state(self) -> ConversationState {
+        self.state
+    }
+
+    /// Returns the queries asked so far with their answers.
+    pub fn turns(&self) -> &[ConversationTurn] {
+        &self.state.turns
+    }
+
+    /// Returns the context the next query follows up on.
+    pub fn follow_up(&self) -> FollowUpContext {
+        self.state.follow_up()
+    }
+
+    /// Builds a request for `query` with the conversation's defaults and
+    /// follow-up context.
+    ///
+    /// Customize it, e.g. with files, and pass it to [`send`](Self::send).
+    pub fn request(&self, query: impl Into<String>) -> SearchRequest {
+        self.state.request(query)
+    }
+
+    /// Asks `query` as the next turn and returns the final response.
+    pub async fn ask(&mut self, query: impl Into<String>) -> Result<SearchResponse> {
+        let request = self.request(query);
+        self.send(request).await
+    }
+
+    /// Sends a request built with [`request`](Self::request) as the next turn.
+    ///
+    /// The Space of the request is kept for following queries.
+    pub async fn send(&mut self, request: SearchRequest) -> Result<SearchResponse> {
+        let query = request.query.clone();
+        let attachment_urls = request.attachment_urls.clone();
+        let space = request.space.clone();
+        let response = self.client.search(request).await?;
+
+        self.state.space = space;
+        self.state.start_turn(query, response.model_preference, attachment_urls);
+        self.state.record_event(&SearchEvent {
+            answer: response.answer.clone(),
+            web_results: response.web_results.clone(),
+            backend_uuid: response.follow_up.backend_uuid.clone(),
+            attachments: response.follow_up.attachments.clone(),
+            ..SearchEvent::default()
+        });
+        Ok(response)
+    }
+
+    /// Asks `query` as the next turn and returns a stream of events.
+    ///
+    /// The conversation is updated as events pass through the stream.
+    pub async fn ask_stream(
+        &mut self,
+        query: impl Into<String>,
+    ) -> Result<impl Stream<Item = Result<SearchEvent>> + '_> {
+        let request = self.request(query);
+        self.send_stream(request).await
+    }
+
+    /// Sends a request built with [`request`](Self::request) as the next
+    /// turn and returns a stream of events.
+    ///
+    /// The Space of the request is kept for following queries.
+    pub async fn send_stream(
+        &mut self,
+        request: SearchRequest,
+    ) -> Result<impl Stream<Item = Result<SearchEvent>> + '_> {
+        let query = request.query.clone();
+        let attachment_urls = request.attachment_urls.clone();
+        let space = request.space.clone();
+        let (model_preference, stream) = self.client.search_stream_with_model(request).await?;
+
+        let state = &mut self.state;
+        state.space = space;
+        state.start_turn(query, model_preference, attachment_urls);
+        Ok(stream.inspect(move |event| {
+            if let Ok(event) = event {
+                state.record_event(event);
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchModel;
+
+    fn event(
+        answer: Option<&str>,
+        backend_uuid: Option<&str>,
+        attachments: &[&str],
+    ) -> SearchEvent {
+        SearchEvent {
+            answer: answer.map(str::to_string),
+            backend_uuid: backend_uuid.map(str::to_string),
+            attachments: attachments.iter().map(|url| (*url).to_string()).collect(),
+            ..SearchEvent::default()
+        }
+    }
+
+    #[test]
+    fn records_turns_and_follow_up_context() {
+        let mut state = ConversationState::default();
+        let model = SearchModel::Turbo.api_preference();
+        state.start_turn("first".to_string(), model, vec!["https://s3/a".to_string()]);
+        state.record_event(&event(Some("partial"), None, &[]));
+        state.record_event(&event(
+            Some("done"),
+            Some("uuid-1"),
+            &["https://s3/a", "https://s3/b"],
+        ));
+        state.record_event(&event(None, None, &[]));
+
+        assert_eq!(state.backend_uuid.as_deref(), Some("uuid-1"));
+        assert_eq!(state.attachments, ["https://s3/a", "https://s3/b"]);
+        assert_eq!(state.turns.len(), 1);
+        assert_eq!(state.turns[0].answer.as_deref(), Some("done"));
+        assert_eq!(state.turns[0].backend_uuid.as_deref(), Some("uuid-1"));
+
+        state.start_turn("second".to_string(), model, Vec::new());
+        state.record_event(&event(Some("again"), Some("uuid-2"), &[]));
+        assert_eq!(state.backend_uuid.as_deref(), Some("uuid-2"));
+        assert_eq!(state.turns[1].answer.as_deref(), Some("again"));
+    }
+
+    #[test]
+    fn carries_space_into_follow_ups() {
+        let mut state = ConversationState {
+            space: Some("space-1".to_string()),
+            ..ConversationState::default()
+        };
+        assert_eq!(state.request("first").space.as_deref(), Some("space-1"));
+
+        let model = SearchModel::Turbo.api_preference();
+        state.start_turn("first".to_string(), model, Vec::new());
+        state.record_event(&event(Some("answer"), Some("uuid-1"), &[]));
+        let request = state.request("second");
+        assert_eq!(request.space.as_deref(), Some("space-1"));
+        let follow_up = request.follow_up.unwrap();
+        assert_eq!(follow_up.backend_uuid.as_deref(), Some("uuid-1"));
+        assert_eq!(follow_up.space.as_deref(), Some("space-1"));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut state = ConversationState {
+            mode: SearchMode::Reasoning,
+            models: vec![crate::ReasonModel::Gpt54Thinking.api_preference()],
+            sources: vec![Source::Scholar],
+            ..ConversationState::default()
+        };
+        state.start_turn("question".to_string(), state.models[0], Vec::new());
+        state.record_event(&event(Some("answer"), Some("uuid-1"), &[]));
+
+        let json = serde_json::to_value(&state).unwrap();
+        assert_eq!(json["mode"], "reasoning");
+        assert_eq!(json["models"][0], "gpt54_thinking");
+        assert_eq!(json["sources"][0], "scholar");
+
+        let restored: ConversationState = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.mode, SearchMode::Reasoning);
+        assert_eq!(restored.models, state.models);
+        assert_eq!(restored.backend_uuid.as_deref(), Some("uuid-1"));
+        assert_eq!(restored.turns[0].answer.as_deref(), Some("answer"));
+        assert_eq!(restored.language, "en-US");
+    }
+
+    #[test]
+    fn rejects_unknown_model_preference() {
+        let json = serde_json::json!({ "models": ["not-a-model"] });
+        assert!(serde_json::from_value::<ConversationState>(json).is_err());
+    }
+}
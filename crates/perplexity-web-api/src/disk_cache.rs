@@ -0,0 +1,77 @@
+//! Files written by the disk backends of the attachment and response caches,
+//! which may share their directory with files they do not own.
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Returns whether `name` is a file a disk cache writes: an entry named after
+/// its SHA-256 key, or a temporary file left behind by an interrupted write.
+pub(crate) fn is_cache_file(name: &str) -> bool {
+    let key = match name.strip_suffix(".tmp") {
+        Some(tmp) => tmp.split_once('.').map(|(key, _)| key),
+        None => name.strip_suffix(".json"),
+    };
+    key.is_some_and(|key| {
+        key.len() == 64
+            && key.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    })
+}
+
+/// Removes the cache files in `dir`, or only those last written longer ago
+/// than `max_age` if given. Other files and the directory itself are kept.
+pub(crate) async fn remove_files(dir: &Path, max_age: Option<Duration>) -> io::Result<()> {
+    let mut files = match tokio::fs::read_dir(dir).await {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        files => files?,
+    };
+    while let Some(file) = files.next_entry().await? {
+        if !file.file_name().to_str().is_some_and(is_cache_file) {
+            continue;
+        }
+        if let Some(max_age) = max_age {
+            let expired = file
+                .metadata()
+                .await
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > max_age));
+            if !expired {
+                continue;
+            }
+        }
+        match tokio::fs::remove_file(file.path()).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn removes_only_cache_files() {
+        let dir = std::env::temp_dir().join(format!("disk-cache-{}", uuid::Uuid::new_v4()));
+        let key = "0123456789abcdef".repeat(4);
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in [
+            format!("{key}.json"),
+            format!("{key}.{}.tmp", uuid::Uuid::new_v4()),
+            "settings.json".to_string(),
+            "notes.txt".to_string(),
+        ] {
+            std::fs::write(dir.join(name), b"{}").unwrap();
+        }
+
+        remove_files(&dir, None).await.unwrap();
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|file| file.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["notes.txt", "settings.json"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
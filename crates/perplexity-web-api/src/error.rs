@@ -1,3 +1,4 @@
+use crate::types::SearchMode;
 use std::time::Duration;
 use thiserror::Error;
 
@@ -48,6 +49,87 @@ pub enum Error {
     #[error("Attachment processing failed: {0}")]
     AttachmentProcessing(#[source] rquest::Error),
 
+    /// The server rejected an attachment during processing.
+    #[error("Attachment '{filename}' was rejected: {reason}")]
+    AttachmentRejected { filename: String, reason: String },
+
+    /// The processing stream ended without reporting on an attachment, so it
+    /// is unknown whether the server can use it.
+    #[error("Processing of attachment '{filename}' was not confirmed")]
+    AttachmentUnconfirmed { filename: String },
+
+    /// An upload batch has more files than the upload policy allows.
+    #[error("Upload batch has {count} files, more than the allowed {limit}")]
+    TooManyFiles { count: usize, limit: usize },
+
+    /// A file is larger than the upload policy allows.
+    #[error("File '{filename}' is {size} bytes, more than the allowed {limit}")]
+    FileTooLarge { filename: String, size: u64, limit: u64 },
+
+    /// The files of an upload batch are larger than the upload policy allows.
+    #[error("Upload batch is {size} bytes, more than the allowed {limit}")]
+    BatchTooLarge { size: u64, limit: u64 },
+
+    /// The upload policy does not allow the content type of a file.
+    #[error("File '{filename}' has content type '{content_type}', which is not allowed")]
+    ContentTypeNotAllowed { filename: String, content_type: String },
+
+    /// Downloading an attachment failed.
+    #[error("Attachment download failed: {0}")]
+    AttachmentDownload(#[source] rquest::Error),
+
+    /// Reading a file or reader upload source failed.
+    #[error("Failed to read upload source: {0}")]
+    UploadSource(#[source] std::io::Error),
+
+    /// A reader upload source was already consumed by a previous upload.
+    #[error("Upload source for '{0}' was already consumed")]
+    UploadSourceConsumed(String),
+
+    /// The thread library requires authentication cookies.
+    #[error("Thread history requires authentication cookies")]
+    ThreadsRequireAuth,
+
+    /// Thread library request failed.
+    #[error("Thread request failed: {0}")]
+    ThreadRequest(#[source] rquest::Error),
+
+    /// The thread has no entries to operate on.
+    #[error("Thread '{0}' not found")]
+    ThreadNotFound(String),
+
+    /// Spaces require authentication cookies.
+    #[error("Spaces require authentication cookies")]
+    SpacesRequireAuth,
+
+    /// Space request failed.
+    #[error("Space request failed: {0}")]
+    SpaceRequest(#[source] rquest::Error),
+
+    /// The follow-up context lacks the backend UUID or query to rewrite.
+    #[error("Follow-up context has no answer to rewrite")]
+    NothingToRewrite,
+
+    /// The client's rate limiter would delay the request beyond its maximum wait.
+    #[error("Rate limit for {mode:?} searches would delay the request by {wait:?}")]
+    RateLimited { mode: SearchMode, wait: Duration },
+
+    /// A middleware rejected the request or one of its events.
+    #[error("Rejected by middleware: {0}")]
+    Rejected(String),
+
+    /// A comparison was requested without any model.
+    #[error("No models to compare")]
+    NoModelsToCompare,
+
+    /// Quota requests require authentication cookies.
+    #[error("Quota requests require authentication cookies")]
+    QuotaRequiresAuth,
+
+    /// Quota request failed.
+    #[error("Quota request failed: {0}")]
+    QuotaRequest(#[source] rquest::Error),
+
     /// Invalid MIME type.
     #[error("Invalid MIME type: {0}")]
     InvalidMimeType(String),
@@ -68,5 +150,38 @@ pub enum Error {
     InvalidBaseUrl,
 }
 
+impl Error {
+    /// Returns `true` if the error means the requested model is temporarily
+    /// unavailable, so retrying with another model may succeed.
+    ///
+    /// A 429 throttles the whole account rather than one model, so it is not
+    /// counted: every other model would be throttled as well.
+    pub fn is_model_unavailable(&self) -> bool {
+        matches!(self, Self::Server { status: 503, .. })
+    }
+
+    /// Returns `true` if the error may pass on its own, so sending the same
+    /// ask request again may succeed.
+    ///
+    /// Only errors the server answers with before accepting a query count.
+    /// Timeouts, transport failures and streams cut short may come after the
+    /// query was accepted, and sending it again would spend another one.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Server { status: 429 | 500 | 502 | 503 | 504, .. })
+    }
+
+    /// Returns `true` if files were refused by the client's upload policy
+    /// before anything was sent.
+    pub fn is_upload_policy_violation(&self) -> bool {
+        matches!(
+            self,
+            Self::TooManyFiles { .. }
+                | Self::FileTooLarge { .. }
+                | Self::BatchTooLarge { .. }
+                | Self::ContentTypeNotAllowed { .. }
+        )
+    }
+}
+
 /// Convenience Result type for this crate.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,437 @@
+//! Export of responses, threads and conversations to Markdown, JSON and HTML.
+
+use crate::conversation::ConversationState;
+use crate::types::{SearchResponse, SearchWebResult, Thread};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Output format of an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Markdown transcript with a sources section per turn.
+    Markdown,
+    /// Lossless JSON including the raw events and their steps.
+    Json,
+    /// Standalone HTML report.
+    Html,
+}
+
+impl ExportFormat {
+    /// Returns the format matching a file extension (`md`, `json`, `html`).
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            "html" | "htm" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    /// Returns the usual file extension of the format.
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// Queries and answers prepared for export.
+///
+/// Responses, threads and conversations are converted to a transcript
+/// first, which renders to any [`ExportFormat`]:
+///
+/// ```no_run
+/// use perplexity_web_api::{Client, ExportFormat, SearchMode, SearchRequest, Transcript};
+///
+/// # async fn example(client: Client) -> Result<(), Box<dyn std::error::Error>> {
+/// let query = "State of solid-state batteries";
+/// let response =
+///     client.search(SearchRequest::new(query).mode(SearchMode::DeepResearch)).await?;
+///
+/// let transcript = Transcript::from_response(Some(query), &response);
+/// transcript.write("report.html", ExportFormat::Html).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Only `http`, `https` and `mailto` URLs become links; any other URL is
+/// rendered as text.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Transcript {
+    /// Title of the document.
+    pub title: String,
+    /// Shareable thread URL, if known.
+    pub url: Option<String>,
+    /// Queries and answers, oldest first.
+    pub turns: Vec<TranscriptTurn>,
+}
+
+/// A query and its answer within a [`Transcript`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranscriptTurn {
+    /// The query string, if known.
+    pub query: Option<String>,
+    /// The answer text.
+    pub answer: Option<String>,
+    /// Web search results cited by the answer.
+    pub web_results: Vec<SearchWebResult>,
+    /// Model that produced the answer.
+    pub model: Option<String>,
+    /// Steps of the answer (searches, reasoning, final answer) from the raw event.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<TranscriptStep>,
+    /// The raw event, `Null` when not kept.
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub raw: Value,
+}
+
+/// A step of an answer, such as a search or the final answer.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranscriptStep {
+    /// Type of the step, e.g. `"SEARCH"` or `"FINAL"`.
+    pub step_type: String,
+    /// Content of the step as sent by the server.
+    #[serde(default)]
+    pub content: Value,
+}
+
+impl Transcript {
+    /// Creates a transcript of a single response to `query`.
+    ///
+    /// Steps and the raw event are included when the client keeps raw
+    /// events (the default).
+    pub fn from_response(query: Option<&str>, response: &SearchResponse) -> Self {
+        let model = response
+            .metadata
+            .display_model
+            .clone()
+            .unwrap_or_else(|| response.model_preference.as_str().to_string());
+        Self {
+            title: query.unwrap_or("Perplexity answer").to_string(),
+            url: response.metadata.thread_url(),
+            turns: vec![TranscriptTurn {
+                query: query.map(str::to_string),
+                answer: response.answer.clone(),
+                web_results: response.web_results.clone(),
+                model: Some(model),
+                steps: steps_from_raw(&response.raw),
+                raw: response.raw.clone(),
+            }],
+        }
+    }
+
+    /// Creates a transcript of every entry of a thread.
+    pub fn from_thread(thread: &Thread) -> Self {
+        Self {
+            title: thread.entries.first().map(|e| e.query.clone()).unwrap_or_default(),
+            url: Some(thread.url()),
+            turns: thread
+                .entries
+                .iter()
+                .map(|entry| TranscriptTurn {
+                    query: Some(entry.query.clone()),
+                    answer: entry.answer.clone(),
+                    web_results: entry.web_results.clone(),
+                    model: entry.display_model.clone(),
+                    ..TranscriptTurn::default()
+                })
+                .collect(),
+        }
+    }
+
+    /// Creates a transcript of the turns of a conversation.
+    pub fn from_conversation(state: &ConversationState) -> Self {
+        Self {
+            title: state.turns.first().map(|t| t.query.clone()).unwrap_or_default(),
+            url: None,
+            turns: state
+                .turns
+                .iter()
+                .map(|turn| TranscriptTurn {
+                    query: Some(turn.query.clone()),
+                    answer: turn.answer.clone(),
+                    web_results: turn.web_results.clone(),
+                    model: Some(turn.model_preference.as_str().to_string()),
+                    ..TranscriptTurn::default()
+                })
+                .collect(),
+        }
+    }
+
+    /// Renders the transcript in `format`.
+    pub fn render(&self, format: ExportFormat) -> serde_json::Result<String> {
+        match format {
+            ExportFormat::Markdown => Ok(self.to_markdown()),
+            ExportFormat::Json => self.to_json(),
+            ExportFormat::Html => Ok(self.to_html()),
+        }
+    }
+
+    /// Renders the transcript in `format` and writes it to `path`.
+    pub async fn write(
+        &self,
+        path: impl AsRef<Path>,
+        format: ExportFormat,
+    ) -> std::io::Result<()> {
+        let contents = self.render(format)?;
+        tokio::fs::write(path, contents).await
+    }
+
+    /// Renders a Markdown transcript with a numbered sources section per
+    /// turn, matching the `[n]` citations of the answers.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n", self.title);
+        if let Some(url) = &self.url {
+            if is_linkable(url) {
+                let _ = write!(out, "\n<{url}>\n");
+            } else {
+                let _ = write!(out, "\n`{url}`\n");
+            }
+        }
+        for turn in &self.turns {
+            if let Some(query) = &turn.query {
+                let _ = write!(out, "\n## {query}\n");
+            }
+            if let Some(model) = &turn.model {
+                let _ = write!(out, "\n*Model: {model}*\n");
+            }
+            if let Some(answer) = &turn.answer {
+                let _ = write!(out, "\n{}\n", answer.trim_end());
+            }
+            if !turn.web_results.is_empty() {
+                out.push_str("\n### Sources\n\n");
+                for (index, result) in turn.web_results.iter().enumerate() {
+                    if is_linkable(&result.url) {
+                        let _ =
+                            write!(out, "{}. [{}]({})", index + 1, result.name, result.url);
+                    } else {
+                        let _ =
+                            write!(out, "{}. {} (`{}`)", index + 1, result.name, result.url);
+                    }
+                    if !result.snippet.is_empty() {
+                        let _ = write!(out, " — {}", result.snippet);
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+        out
+    }
+
+    /// Renders the transcript as pretty-printed JSON, including steps and
+    /// raw events.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Renders a standalone HTML report.
+    ///
+    /// Answers are rendered from Markdown; HTML embedded in answers is
+    /// escaped rather than passed through.
+    pub fn to_html(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n",
+        );
+        let _ = writeln!(out, "<title>{}</title>", escape_html(&self.title));
+        out.push_str(HTML_STYLE);
+        out.push_str("</head>\n<body>\n");
+        let _ = writeln!(out, "<h1>{}</h1>", escape_html(&self.title));
+        if let Some(url) = &self.url {
+            out.push_str("<p class=\"thread\">");
+            push_link(&mut out, url, url);
+            out.push_str("</p>\n");
+        }
+        for turn in &self.turns {
+            out.push_str("<section>\n");
+            if let Some(query) = &turn.query {
+                let _ = writeln!(out, "<h2>{}</h2>", escape_html(query));
+            }
+            if let Some(model) = &turn.model {
+                let _ = writeln!(out, "<p class=\"model\">Model: {}</p>", escape_html(model));
+            }
+            if let Some(answer) = &turn.answer {
+                out.push_str("<div class=\"answer\">\n");
+                render_markdown(&mut out, answer);
+                out.push_str("</div>\n");
+            }
+            if !turn.web_results.is_empty() {
+                out.push_str("<h3>Sources</h3>\n<ol class=\"sources\">\n");
+                for result in &turn.web_results {
+                    out.push_str("<li>");
+                    push_link(&mut out, &result.url, &result.name);
+                    if !result.snippet.is_empty() {
+                        let _ = write!(out, "<p>{}</p>", escape_html(&result.snippet));
+                    }
+                    out.push_str("</li>\n");
+                }
+                out.push_str("</ol>\n");
+            }
+            out.push_str("</section>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+const HTML_STYLE: &str = "<style>\n\
+body { font-family: system-ui, sans-serif; max-width: 50rem; margin: 2rem auto; \
+padding: 0 1rem; line-height: 1.6; color: #1f2328; }\n\
+section { border-top: 1px solid #d0d7de; margin-top: 2rem; }\n\
+.model, .thread { color: #59636e; font-size: 0.9rem; }\n\
+.sources p { margin: 0.2rem 0 0.6rem; color: #59636e; font-size: 0.9rem; }\n\
+pre { overflow-x: auto; background: #f6f8fa; padding: 1rem; }\n\
+</style>\n";
+
+/// Extracts the steps from the "text" field of a raw event.
+fn steps_from_raw(raw: &Value) -> Vec<TranscriptStep> {
+    let Some(steps) = raw.get("text").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+    steps.iter().filter_map(|step| TranscriptStep::deserialize(step).ok()).collect()
+}
+
+/// Renders Markdown to HTML, escaping embedded HTML.
+///
+/// Links and images with a URL that is not [linkable](is_linkable) keep
+/// only their text.
+fn render_markdown(out: &mut String, markdown: &str) {
+    // Whether each open link or image was dropped, to drop its end as well.
+    let mut dropped = Vec::new();
+    let events = Parser::new(markdown).filter_map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Some(Event::Text(html)),
+        Event::Start(Tag::Link { ref dest_url, .. } | Tag::Image { ref dest_url, .. }) => {
+            let linkable = is_linkable(dest_url);
+            dropped.push(!linkable);
+            linkable.then_some(event)
+        }
+        Event::End(TagEnd::Link | TagEnd::Image) => {
+            (!dropped.pop().unwrap_or(false)).then_some(event)
+        }
+        event => Some(event),
+    });
+    pulldown_cmark::html::push_html(out, events);
+}
+
+/// Returns `true` if `url` has a scheme that is safe to link to.
+fn is_linkable(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        ["http", "https", "mailto"].iter().any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+    })
+}
+
+/// Writes an escaped link to `url`, or only its escaped text if the URL is
+/// not [linkable](is_linkable).
+fn push_link(out: &mut String, url: &str, text: &str) {
+    if is_linkable(url) {
+        let _ = write!(out, "<a href=\"{}\">{}</a>", escape_html(url), escape_html(text));
+    } else {
+        out.push_str(&escape_html(text));
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript() -> Transcript {
+        Transcript {
+            title: "Rust <history>".to_string(),
+            url: Some("https://www.perplexity.ai/search/rust-abc".to_string()),
+            turns: vec![TranscriptTurn {
+                query: Some("Who designed Rust?".to_string()),
+                answer: Some("**Graydon Hoare** [1]. <script>x</script>".to_string()),
+                web_results: vec![SearchWebResult {
+                    name: "Rust".to_string(),
+                    url: "https://rust-lang.org".to_string(),
+                    snippet: "A language".to_string(),
+                }],
+                model: Some("turbo".to_string()),
+                ..TranscriptTurn::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn renders_markdown_with_sources() {
+        let markdown = transcript().to_markdown();
+        assert!(markdown.starts_with("# Rust <history>\n"));
+        assert!(markdown.contains("\n## Who designed Rust?\n"));
+        assert!(
+            markdown
+                .contains("### Sources\n\n1. [Rust](https://rust-lang.org) — A language\n")
+        );
+    }
+
+    #[test]
+    fn renders_escaped_html() {
+        let html = transcript().to_html();
+        assert!(html.contains("<title>Rust &lt;history&gt;</title>"));
+        assert!(html.contains("<strong>Graydon Hoare</strong>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<a href=\"https://rust-lang.org\">Rust</a>"));
+    }
+
+    #[test]
+    fn renders_unsafe_urls_as_text() {
+        let mut transcript = transcript();
+        transcript.url = Some("javascript:alert(1)".to_string());
+        transcript.turns[0].answer = Some(
+            "[click](javascript:alert(1)) ![img](data:text/html,x) [ok](https://a.example/?q=\"x\")"
+                .to_string(),
+        );
+        transcript.turns[0].web_results[0].url = "JavaScript:alert(1)".to_string();
+        transcript.turns[0].web_results[0].name = "<b>Rust</b>".to_string();
+
+        let html = transcript.to_html();
+        assert!(!html.to_ascii_lowercase().contains("javascript:alert(1)\""));
+        assert!(!html.contains("href=\"javascript") && !html.contains("src=\"data"));
+        assert!(html.contains("<p class=\"thread\">javascript:alert(1)</p>"));
+        assert!(html.contains("click img <a href=\"https://a.example/?q=%22x%22\">ok</a>"));
+        assert!(html.contains("<li>&lt;b&gt;Rust&lt;/b&gt;<p>"));
+
+        let markdown = transcript.to_markdown();
+        assert!(markdown.contains("\n`javascript:alert(1)`\n"));
+        assert!(markdown.contains("1. <b>Rust</b> (`JavaScript:alert(1)`)"));
+    }
+
+    #[test]
+    fn keeps_steps_in_json() {
+        let raw = serde_json::json!({
+            "text": [{"step_type": "SEARCH", "content": {"queries": ["rust"]}}],
+        });
+        let mut transcript = transcript();
+        transcript.turns[0].steps = steps_from_raw(&raw);
+        transcript.turns[0].raw = raw;
+
+        let json: Value = serde_json::from_str(&transcript.to_json().unwrap()).unwrap();
+        assert_eq!(json["turns"][0]["steps"][0]["step_type"], "SEARCH");
+        assert_eq!(json["turns"][0]["raw"]["text"][0]["content"]["queries"][0], "rust");
+    }
+
+    #[test]
+    fn picks_format_from_extension() {
+        assert_eq!(ExportFormat::from_extension("MD"), Some(ExportFormat::Markdown));
+        assert_eq!(ExportFormat::from_extension("htm"), Some(ExportFormat::Html));
+        assert_eq!(ExportFormat::from_extension("pdf"), None);
+    }
+}
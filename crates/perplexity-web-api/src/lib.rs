@@ -67,6 +67,51 @@
 //! # }
 //! ```
 //!
+//! # Reusing Uploaded Files
+//!
+//! Files can be uploaded once and attached to many searches by URL:
+//!
+//! ```no_run
+//! use perplexity_web_api::{Client, SearchRequest, UploadFile};
+//!
+//! # async fn example(client: Client) -> perplexity_web_api::Result<()> {
+//! let report = UploadFile::from_path("report.pdf")
+//!     .await
+//!     .map_err(perplexity_web_api::Error::UploadSource)?;
+//! let urls = client.upload_files(&[&report]).await?;
+//!
+//! for question in ["Summarize the report", "List the key risks"] {
+//!     let response = client
+//!         .search(SearchRequest::new(question).attachment_urls(urls.iter().cloned()))
+//!         .await?;
+//!     println!("{}", response.answer.unwrap_or_default());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Asking Again With Another Model
+//!
+//! A query can be asked again with another model, as a follow-up of its answer:
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use perplexity_web_api::{Client, SearchModel, SearchMode, SearchRequest};
+//!
+//! # async fn example(client: Client) -> perplexity_web_api::Result<()> {
+//! let response =
+//!     client.search(SearchRequest::new("Explain borrow checking").mode(SearchMode::Pro)).await?;
+//!
+//! let mut stream = Box::pin(client.rewrite(&response.follow_up, SearchModel::Claude46Sonnet).await?);
+//! while let Some(event) = stream.next().await {
+//!     if let Some(answer) = event?.answer {
+//!         println!("{answer}");
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! # Search Modes
 //!
 //! - [`SearchMode::Auto`] - Default mode, uses the turbo model
@@ -79,22 +124,55 @@
 //! - [`Source::Web`] - General web search (default)
 //! - [`Source::Scholar`] - Academic papers and research
 //! - [`Source::Social`] - Social media content
+//!
+//! # Cargo Features
+//!
+//! - `schemars` - Derives `JsonSchema` for request and response types
+//! - `tracing` - Emits `tracing` spans at debug level for session warm-up,
+//!   searches, upload steps and event streams. Searches record their mode,
+//!   model, sources and query length; streams report the events and bytes
+//!   received, their duration and why they ended
+//! - `tracing-queries` - Also records the query text in search spans
 
+mod attachment_cache;
 mod auth;
+mod batch;
 mod client;
+mod compare;
 mod config;
+mod conversation;
+mod disk_cache;
 mod error;
+mod export;
+mod middleware;
 mod models;
 mod parse;
+mod quota;
+mod rate_limit;
+mod response_cache;
+mod rest;
+mod sniff;
+mod spaces;
 mod sse;
+mod threads;
+mod trace;
 mod types;
 mod upload;
 
+pub use attachment_cache::AttachmentCache;
 pub use auth::{AuthCookies, CSRF_TOKEN_COOKIE_NAME, SESSION_TOKEN_COOKIE_NAME};
 pub use client::{Client, ClientBuilder};
+pub use conversation::{Conversation, ConversationState, ConversationTurn};
 pub use error::{Error, Result};
+pub use export::{ExportFormat, Transcript, TranscriptStep, TranscriptTurn};
+pub use middleware::{Middleware, OutgoingRequest, RequestKind};
 pub use models::{ModelPreference, ReasonModel, SearchModel};
+pub use rate_limit::{RateLimit, RateLimiter};
+pub use response_cache::{CacheStats, ResponseCache};
 pub use types::{
-    FollowUpContext, SearchEvent, SearchMode, SearchRequest, SearchResponse, SearchWebResult,
-    Source, UploadFile,
+    AttachmentOutcome, AttachmentStatus, BatchLimits, Comparison, ComparisonEntry,
+    FollowUpContext, Pagination, Quota, ResponseMetadata, ResponseTimings, RetryPolicy,
+    SearchEvent, SearchMode, SearchRequest, SearchResponse, SearchWebResult, Source, Space,
+    SpaceFile, Thread, ThreadEntry, ThreadSummary, UploadFile, UploadOptions, UploadPolicy,
+    UploadProgress, UploadProgressHandler, UploadReader, UploadStage, Usage,
 };
@@ -0,0 +1,168 @@
+use crate::error::Result;
+use crate::types::SearchEvent;
+use rquest::RequestBuilder;
+use std::sync::Arc;
+
+/// Hook around the requests a [`Client`](crate::Client) sends and the events
+/// it receives, registered with
+/// [`ClientBuilder::middleware`](crate::ClientBuilder::middleware).
+///
+/// Middleware runs in registration order. Returning an error from a hook
+/// short-circuits: the request is not sent, or the search fails with that
+/// error. [`Error::Rejected`](crate::Error::Rejected) suits vetoed requests.
+///
+/// ```
+/// use perplexity_web_api::{Error, Middleware, OutgoingRequest, RequestKind, Result};
+///
+/// struct Denylist(Vec<String>);
+///
+/// impl Middleware for Denylist {
+///     fn on_request(&self, request: &mut OutgoingRequest) -> Result<()> {
+///         let query = request.body["query_str"].as_str().unwrap_or_default();
+///         if request.kind == RequestKind::Ask && self.0.iter().any(|word| query.contains(word)) {
+///             return Err(Error::Rejected("query matches the denylist".to_string()));
+///         }
+///         request.headers.push(("x-team".to_string(), "research".to_string()));
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Called before a request is sent, with its JSON body.
+    ///
+    /// The body and headers may be changed, e.g. to redact the query. Searches
+    /// answered from the [response cache](crate::ClientBuilder::response_cache)
+    /// are passed here too, but their request is not sent.
+    fn on_request(&self, request: &mut OutgoingRequest) -> Result<()> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called for each event received from a search.
+    ///
+    /// [`Client::search`](crate::Client::search) only parses the final event,
+    /// so it is the only one seen there, as it is for cached answers;
+    /// streaming searches pass every event.
+    fn on_event(&self, event: &SearchEvent) -> Result<()> {
+        let _ = event;
+        Ok(())
+    }
+}
+
+/// Kind of an [`OutgoingRequest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    /// A search query sent to the ask endpoint.
+    Ask,
+    /// Registration of a batch of file uploads. The file contents sent to
+    /// storage afterwards are not passed to middleware.
+    Upload,
+}
+
+/// A request about to be sent, as seen by [`Middleware::on_request`].
+#[derive(Debug, Clone)]
+pub struct OutgoingRequest {
+    /// What the request does.
+    pub kind: RequestKind,
+    /// URL the request is sent to.
+    pub url: String,
+    /// Extra headers to send with the request.
+    pub headers: Vec<(String, String)>,
+    /// JSON body of the request.
+    ///
+    /// An [`Ask`](RequestKind::Ask) body holds the query in `query_str` and
+    /// its options in `params`, among them `mode`, `model_preference`,
+    /// `sources`, `language`, `is_incognito` and the `attachments` URLs. An
+    /// [`Upload`](RequestKind::Upload) body lists the files in `files`, keyed
+    /// by an id, each with its `filename`, `content_type` and `file_size`.
+    pub body: serde_json::Value,
+}
+
+/// Middleware registered on a client, shared by its clones.
+#[derive(Clone, Default)]
+pub(crate) struct MiddlewareStack(Arc<[Arc<dyn Middleware>]>);
+
+impl MiddlewareStack {
+    pub(crate) fn new(middleware: Vec<Arc<dyn Middleware>>) -> Self {
+        Self(middleware.into())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Runs the request hooks, returning the possibly changed request.
+    pub(crate) fn on_request(
+        &self,
+        kind: RequestKind,
+        url: String,
+        body: serde_json::Value,
+    ) -> Result<OutgoingRequest> {
+        let mut request = OutgoingRequest { kind, url, headers: Vec::new(), body };
+        for middleware in self.0.iter() {
+            middleware.on_request(&mut request)?;
+        }
+        Ok(request)
+    }
+
+    /// Runs the event hooks.
+    pub(crate) fn on_event(&self, event: &SearchEvent) -> Result<()> {
+        self.0.iter().try_for_each(|middleware| middleware.on_event(event))
+    }
+}
+
+impl OutgoingRequest {
+    /// Builds the request with `http`, adding the extra headers and the body.
+    pub(crate) fn build(&self, http: &rquest::Client) -> RequestBuilder {
+        self.headers
+            .iter()
+            .fold(http.post(&self.url), |builder, (name, value)| {
+                builder.header(name.as_str(), value.as_str())
+            })
+            .json(&self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    struct Redact;
+
+    impl Middleware for Redact {
+        fn on_request(&self, request: &mut OutgoingRequest) -> Result<()> {
+            request.body["query_str"] = "[redacted]".into();
+            Ok(())
+        }
+    }
+
+    struct Veto;
+
+    impl Middleware for Veto {
+        fn on_request(&self, _: &mut OutgoingRequest) -> Result<()> {
+            Err(Error::Rejected("vetoed".to_string()))
+        }
+
+        fn on_event(&self, _: &SearchEvent) -> Result<()> {
+            Err(Error::Rejected("vetoed".to_string()))
+        }
+    }
+
+    #[test]
+    fn runs_hooks_in_order() {
+        let body = serde_json::json!({ "query_str": "secret" });
+        let redacting = MiddlewareStack::new(vec![Arc::new(Redact)]);
+        let request =
+            redacting.on_request(RequestKind::Ask, "url".to_string(), body.clone()).unwrap();
+        assert_eq!(request.body["query_str"], "[redacted]");
+        assert!(redacting.on_event(&SearchEvent::default()).is_ok());
+
+        let vetoing = MiddlewareStack::new(vec![Arc::new(Redact), Arc::new(Veto)]);
+        assert!(matches!(
+            vetoing.on_request(RequestKind::Ask, "url".to_string(), body),
+            Err(Error::Rejected(_))
+        ));
+        assert!(vetoing.on_event(&SearchEvent::default()).is_err());
+    }
+}
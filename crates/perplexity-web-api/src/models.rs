@@ -1,3 +1,7 @@
+use crate::error;
+use crate::types::SearchMode;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::str::FromStr;
 
@@ -8,10 +12,51 @@ pub const DEEP_RESEARCH_MODEL_PREFERENCE: &str = "pplx_alpha";
 pub struct ModelPreference(&'static str);
 
 impl ModelPreference {
+    pub(crate) const fn new(preference: &'static str) -> Self {
+        Self(preference)
+    }
+
     /// Returns the raw API model preference value.
     pub const fn as_str(&self) -> &'static str {
         self.0
     }
+
+    /// Returns the search mode that serves this model preference.
+    pub(crate) fn search_mode(&self) -> SearchMode {
+        if *self == SearchModel::Turbo.api_preference() {
+            SearchMode::Auto
+        } else if self.0 == DEEP_RESEARCH_MODEL_PREFERENCE {
+            SearchMode::DeepResearch
+        } else if ReasonModel::ALL.iter().any(|model| model.api_preference() == *self) {
+            SearchMode::Reasoning
+        } else {
+            SearchMode::Pro
+        }
+    }
+
+    /// Returns the known model preference with the given raw API value.
+    pub fn from_api_str(value: &str) -> Option<Self> {
+        SearchModel::ALL
+            .iter()
+            .map(SearchModel::api_preference)
+            .chain(ReasonModel::ALL.iter().map(ReasonModel::api_preference))
+            .chain([Self(DEEP_RESEARCH_MODEL_PREFERENCE)])
+            .find(|preference| preference.0 == value)
+    }
+}
+
+impl Serialize for ModelPreference {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ModelPreference {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::from_api_str(&value)
+            .ok_or_else(|| D::Error::custom(format!("unknown model preference '{value}'")))
+    }
 }
 
 macro_rules! define_model_enum {
@@ -120,3 +165,90 @@ define_model_enum! {
         Claude46SonnetThinking => { name: "claude-4.6-sonnet-thinking", preference: "claude46sonnetthinking" },
     }
 }
+
+/// Sends with `primary`, then with each of `fallbacks` in turn while the
+/// previous model is unavailable, returning the model that was accepted.
+pub(crate) async fn try_model_chain<T, F, Fut>(
+    primary: ModelPreference,
+    fallbacks: &[ModelPreference],
+    mut send: F,
+) -> error::Result<(ModelPreference, T)>
+where
+    F: FnMut(ModelPreference) -> Fut,
+    Fut: Future<Output = error::Result<T>>,
+{
+    let mut model = primary;
+    let mut fallbacks = fallbacks.iter().copied();
+    loop {
+        match send(model).await {
+            Ok(value) => return Ok((model, value)),
+            Err(e) if e.is_model_unavailable() => match fallbacks.next() {
+                Some(next) => model = next,
+                None => return Err(e),
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, Result};
+    use std::cell::RefCell;
+
+    fn server(status: u16) -> Error {
+        Error::Server { status, message: String::new() }
+    }
+
+    async fn walk(responses: Vec<Result<()>>) -> (Result<ModelPreference>, Vec<&'static str>) {
+        let chain = [SearchModel::Claude46Sonnet.into(), SearchModel::Sonar.into()];
+        let responses = RefCell::new(responses.into_iter());
+        let tried = RefCell::new(Vec::new());
+        let result = try_model_chain(SearchModel::Gpt54.into(), &chain, |model| {
+            tried.borrow_mut().push(model.as_str());
+            let response = responses.borrow_mut().next().unwrap();
+            async move { response }
+        })
+        .await
+        .map(|(model, ())| model);
+        (result, tried.into_inner())
+    }
+
+    #[tokio::test]
+    async fn falls_back_while_models_are_unavailable() {
+        let (result, tried) = walk(vec![Err(server(503)), Ok(())]).await;
+        assert_eq!(result.unwrap(), SearchModel::Claude46Sonnet.into());
+        assert_eq!(tried.len(), 2);
+
+        let (result, tried) =
+            walk(vec![Err(server(503)), Err(server(503)), Err(server(503))]).await;
+        assert!(matches!(result, Err(Error::Server { status: 503, .. })));
+        assert_eq!(tried.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn stops_on_throttling_and_other_errors() {
+        let (result, tried) = walk(vec![Err(server(429))]).await;
+        assert!(matches!(result, Err(Error::Server { status: 429, .. })));
+        assert_eq!(tried.len(), 1);
+
+        let (result, tried) = walk(vec![Err(server(400))]).await;
+        assert!(result.is_err());
+        assert_eq!(tried.len(), 1);
+    }
+
+    #[test]
+    fn maps_model_preferences_to_search_modes() {
+        assert_eq!(SearchModel::Turbo.api_preference().search_mode(), SearchMode::Auto);
+        assert_eq!(SearchModel::Gpt54.api_preference().search_mode(), SearchMode::Pro);
+        assert_eq!(
+            ReasonModel::Gemini31Pro.api_preference().search_mode(),
+            SearchMode::Reasoning
+        );
+        assert_eq!(
+            ModelPreference::new(DEEP_RESEARCH_MODEL_PREFERENCE).search_mode(),
+            SearchMode::DeepResearch
+        );
+    }
+}
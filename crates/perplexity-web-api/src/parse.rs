@@ -1,21 +1,87 @@
 use crate::error::{Error, Result};
 use crate::types::{SearchEvent, SearchWebResult};
-use serde::Deserialize;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
 use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+/// The top-level fields of an SSE event.
+///
+/// `T` is the representation of the "text" field: a borrowed [`RawValue`]
+/// when parsing straight from the payload, or [`IgnoredAny`] when the field
+/// is read from an already expanded `raw` value.
+///
+/// A field of an unexpected type is read as missing rather than failing the
+/// whole event.
+#[derive(Deserialize)]
+struct EventFields<T> {
+    text: Option<T>,
+    #[serde(default, deserialize_with = "lenient")]
+    answer: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    backend_uuid: Option<String>,
+    #[serde(default, deserialize_with = "strings")]
+    attachments: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "lenient")]
+    display_model: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    mode: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    thread_url_slug: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    status: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    final_sse_message: Option<bool>,
+    #[serde(default, rename = "final", deserialize_with = "lenient")]
+    is_final: Option<bool>,
+    #[serde(default, deserialize_with = "lenient")]
+    frontend_uuid: Option<String>,
+}
+
+/// Deserializes a value of type `T`, or `None` if the value has another type.
+fn lenient<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    Ok(T::deserialize(Value::deserialize(deserializer)?).ok())
+}
+
+/// Deserializes the strings of an array, dropping entries of other types.
+fn strings<'de, D>(deserializer: D) -> std::result::Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Array(values) => Some(
+            values
+                .into_iter()
+                .filter_map(|value| match value {
+                    Value::String(string) => Some(string),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        _ => None,
+    })
+}
 
 /// A step in the Perplexity response "text" array.
 #[derive(Deserialize)]
-struct TextStep {
-    step_type: String,
-    #[serde(default)]
-    content: StepContent,
+struct TextStep<'a> {
+    #[serde(borrow)]
+    step_type: Cow<'a, str>,
+    #[serde(default, borrow)]
+    content: StepContent<'a>,
 }
 
 /// Content of a single response step.
 #[derive(Deserialize, Default)]
-struct StepContent {
+struct StepContent<'a> {
     /// For FINAL steps, a JSON-encoded string containing the answer and web_results.
-    answer: Option<String>,
+    #[serde(borrow)]
+    answer: Option<Cow<'a, str>>,
 }
 
 /// The decoded payload of a FINAL step's "answer" JSON string.
@@ -26,8 +92,90 @@ struct FinalAnswerData {
     web_results: Vec<SearchWebResult>,
 }
 
+/// The fields of an SSE event that mark it as the final message.
+///
+/// Deserializing only these skips the rest of the payload without building
+/// intermediate values.
+#[derive(Deserialize)]
+struct FinalMarker {
+    #[serde(default)]
+    final_sse_message: bool,
+    #[serde(default, rename = "final")]
+    is_final: bool,
+}
+
+/// Returns `true` if the SSE event payload is marked as the final message.
+///
+/// Payloads that fail to parse are reported as not final; the full parse
+/// surfaces the error.
+pub(crate) fn is_final_event(json: &[u8]) -> bool {
+    serde_json::from_slice::<FinalMarker>(json)
+        .is_ok_and(|marker| marker.final_sse_message || marker.is_final)
+}
+
+/// A single event of the attachment-processing SSE stream.
+///
+/// The stream is not documented, so statuses are matched case-insensitively
+/// against the spellings known to end processing. Any other status counts as
+/// progress, leaving a file that never reaches a known one unconfirmed.
+#[derive(Deserialize)]
+pub(crate) struct ProcessingEvent {
+    pub file_uuid: Option<String>,
+    pub status: Option<String>,
+    #[serde(alias = "error_message", alias = "reason")]
+    pub error: Option<String>,
+    #[serde(alias = "num_pages")]
+    pub page_count: Option<u64>,
+    #[serde(alias = "num_chars", alias = "character_count")]
+    pub char_count: Option<u64>,
+    pub truncated: Option<bool>,
+}
+
+impl ProcessingEvent {
+    /// Returns the reason the file was rejected, if processing failed.
+    pub(crate) fn failure_reason(&self) -> Option<String> {
+        let failed = self.status.as_deref().is_some_and(|status| {
+            ["failed", "failure", "error", "rejected", "unsupported"]
+                .iter()
+                .any(|failed| status.eq_ignore_ascii_case(failed))
+        });
+        match (&self.error, failed) {
+            (Some(error), _) => Some(error.clone()),
+            (None, true) => self.status.clone(),
+            (None, false) => None,
+        }
+    }
+
+    /// Returns `true` if the event reports the end of the file's processing.
+    pub(crate) fn is_terminal(&self) -> bool {
+        self.failure_reason().is_some()
+            || self.status.as_deref().is_some_and(|status| {
+                ["success", "succeeded", "completed", "complete", "processed", "done"]
+                    .iter()
+                    .any(|done| status.eq_ignore_ascii_case(done))
+            })
+    }
+}
+
+/// Parses an attachment-processing SSE event payload.
+pub(crate) fn parse_processing_event(json: &[u8]) -> Result<ProcessingEvent> {
+    serde_json::from_slice(json).map_err(Error::Json)
+}
+
 /// Parses an SSE event JSON string into a SearchEvent.
-pub(crate) fn parse_sse_event(json_str: &str) -> Result<SearchEvent> {
+///
+/// When `keep_raw` is `false`, the event is deserialized directly into the
+/// extracted fields and `raw` is left as [`Value::Null`]. Either way, the
+/// payload, the embedded "text" document and the FINAL answer are each
+/// parsed once.
+pub(crate) fn parse_sse_event(json_str: &str, keep_raw: bool) -> Result<SearchEvent> {
+    if !keep_raw {
+        let fields: EventFields<&RawValue> =
+            serde_json::from_str(json_str).map_err(Error::Json)?;
+        let final_answer = fields.text.and_then(final_answer_from_text);
+        return Ok(into_event(fields, final_answer, Value::Null));
+    }
+
     let mut content: Map<String, Value> =
         serde_json::from_str(json_str).map_err(Error::Json)?;
 
@@ -35,12 +183,44 @@ pub(crate) fn parse_sse_event(json_str: &str) -> Result<SearchEvent> {
     // parsed structure is available in `raw`.
     expand_text_field(&mut content);
 
-    let (answer, web_results) = extract_answer_and_web_results(&content);
-    let backend_uuid = extract_string(&content, "backend_uuid");
-    let attachments = extract_string_array(&content, "attachments");
+    let final_answer = content
+        .get("text")
+        .and_then(|text| Vec::<TextStep>::deserialize(text).ok())
+        .and_then(final_answer_from_steps);
     let raw = Value::Object(content);
+    let fields = EventFields::<IgnoredAny>::deserialize(&raw).map_err(Error::Json)?;
 
-    Ok(SearchEvent { answer, web_results, backend_uuid, attachments, raw })
+    Ok(into_event(fields, final_answer, raw))
+}
+
+/// Assembles a SearchEvent from the parsed fields.
+///
+/// Answer and web_results come from the FINAL step when present, otherwise
+/// from the top-level "answer" field (which carries no web_results).
+fn into_event<T>(
+    fields: EventFields<T>,
+    final_answer: Option<FinalAnswerData>,
+    raw: Value,
+) -> SearchEvent {
+    let (answer, web_results) = match final_answer {
+        Some(data) => (data.answer, data.web_results),
+        None => (fields.answer, Vec::new()),
+    };
+
+    SearchEvent {
+        answer,
+        web_results,
+        backend_uuid: fields.backend_uuid,
+        attachments: fields.attachments.unwrap_or_default(),
+        display_model: fields.display_model,
+        mode: fields.mode,
+        thread_url_slug: fields.thread_url_slug,
+        status: fields.status,
+        is_final: fields.final_sse_message.unwrap_or(false)
+            || fields.is_final.unwrap_or(false),
+        frontend_uuid: fields.frontend_uuid,
+        raw,
+    }
 }
 
 /// If the "text" field is a JSON string, replace it with the parsed value.
@@ -54,47 +234,25 @@ fn expand_text_field(content: &mut Map<String, Value>) {
     }
 }
 
-/// Extracts answer and web_results from the event content.
-///
-/// Tries the FINAL step inside the "text" steps array first, then falls back
-/// to the top-level "answer" field (which carries no web_results).
-fn extract_answer_and_web_results(
-    content: &Map<String, Value>,
-) -> (Option<String>, Vec<SearchWebResult>) {
-    if let Some(result) = extract_from_final_step(content) {
-        return result;
+/// Decodes the steps from the raw "text" field, which is either a JSON array
+/// or a JSON string embedding one, and pulls out the FINAL answer.
+fn final_answer_from_text(text: &RawValue) -> Option<FinalAnswerData> {
+    let json = text.get();
+    if json.starts_with('"') {
+        let embedded: Cow<'_, str> = serde_json::from_str(json).ok()?;
+        final_answer_from_steps(serde_json::from_str(&embedded).ok()?)
+    } else {
+        final_answer_from_steps(serde_json::from_str(json).ok()?)
     }
-    (extract_string(content, "answer"), Vec::new())
 }
 
-/// Deserializes the "text" steps array and pulls answer + web_results from the
-/// FINAL step. Returns `None` when no FINAL step exists or parsing fails.
-fn extract_from_final_step(
-    content: &Map<String, Value>,
-) -> Option<(Option<String>, Vec<SearchWebResult>)> {
-    let text_value = content.get("text")?;
-
-    let steps: Vec<TextStep> = serde_json::from_value(text_value.clone()).ok()?;
-
+/// Decodes the answer + web_results from the FINAL step. Returns `None` when
+/// no FINAL step exists or parsing fails.
+fn final_answer_from_steps(steps: Vec<TextStep<'_>>) -> Option<FinalAnswerData> {
     let final_step = steps.into_iter().find(|s| s.step_type == "FINAL")?;
     let answer_json = final_step.content.answer?;
 
-    let data: FinalAnswerData = serde_json::from_str(&answer_json).ok()?;
-    Some((data.answer, data.web_results))
-}
-
-/// Extracts a string value from the content map.
-fn extract_string(content: &Map<String, Value>, key: &str) -> Option<String> {
-    content.get(key).and_then(|v| v.as_str()).map(str::to_owned)
-}
-
-/// Extracts an array of strings from the content map.
-fn extract_string_array(content: &Map<String, Value>, key: &str) -> Vec<String> {
-    content
-        .get(key)
-        .and_then(|v| v.as_array())
-        .map(|arr| arr.iter().filter_map(|v| v.as_str().map(str::to_owned)).collect())
-        .unwrap_or_default()
+    serde_json::from_str(&answer_json).ok()
 }
 
 #[cfg(test)]
@@ -104,7 +262,7 @@ mod tests {
     #[test]
     fn test_parse_simple_event() {
         let json = r#"{"answer": "Hello world"}"#;
-        let event = parse_sse_event(json).unwrap();
+        let event = parse_sse_event(json, true).unwrap();
 
         assert_eq!(event.answer, Some("Hello world".to_string()));
         assert!(event.web_results.is_empty());
@@ -115,7 +273,7 @@ mod tests {
     #[test]
     fn test_parse_event_with_backend_uuid() {
         let json = r#"{"answer": "Test", "backend_uuid": "abc-123"}"#;
-        let event = parse_sse_event(json).unwrap();
+        let event = parse_sse_event(json, true).unwrap();
 
         assert_eq!(event.answer, Some("Test".to_string()));
         assert_eq!(event.backend_uuid, Some("abc-123".to_string()));
@@ -124,7 +282,7 @@ mod tests {
     #[test]
     fn test_parse_event_with_attachments() {
         let json = r#"{"answer": "Test", "attachments": ["url1", "url2"]}"#;
-        let event = parse_sse_event(json).unwrap();
+        let event = parse_sse_event(json, true).unwrap();
 
         assert_eq!(event.attachments, vec!["url1", "url2"]);
     }
@@ -152,7 +310,7 @@ mod tests {
             "some_field": "value"
         });
 
-        let event = parse_sse_event(&json.to_string()).unwrap();
+        let event = parse_sse_event(&json.to_string(), true).unwrap();
 
         assert_eq!(event.answer, Some("Nested answer".to_string()));
         assert_eq!(event.web_results.len(), 1);
@@ -180,7 +338,7 @@ mod tests {
             "answer": "Top level answer"
         });
 
-        let event = parse_sse_event(&json.to_string()).unwrap();
+        let event = parse_sse_event(&json.to_string(), true).unwrap();
 
         assert_eq!(event.answer, Some("Top level answer".to_string()));
         assert!(event.web_results.is_empty());
@@ -195,7 +353,7 @@ mod tests {
             "extra_field": "should be in raw",
             "another": 123
         }"#;
-        let event = parse_sse_event(json).unwrap();
+        let event = parse_sse_event(json, true).unwrap();
 
         // All keys, including extracted ones, are present in raw
         assert!(event.raw.get("answer").is_some());
@@ -205,20 +363,141 @@ mod tests {
         assert!(event.raw.get("another").is_some());
     }
 
+    #[test]
+    fn test_parse_event_metadata() {
+        let json = r#"{
+            "display_model": "claude46sonnet",
+            "mode": "COPILOT",
+            "thread_url_slug": "what-is-rust-abc123",
+            "status": "COMPLETED",
+            "final_sse_message": true,
+            "frontend_uuid": "front-uuid"
+        }"#;
+        let event = parse_sse_event(json, true).unwrap();
+
+        assert_eq!(event.display_model.as_deref(), Some("claude46sonnet"));
+        assert_eq!(event.mode.as_deref(), Some("COPILOT"));
+        assert_eq!(event.thread_url_slug.as_deref(), Some("what-is-rust-abc123"));
+        assert_eq!(event.status.as_deref(), Some("COMPLETED"));
+        assert!(event.is_final);
+        assert_eq!(event.frontend_uuid.as_deref(), Some("front-uuid"));
+    }
+
+    #[test]
+    fn test_is_final_event() {
+        assert!(is_final_event(br#"{"final_sse_message": true, "text": "[]"}"#));
+        assert!(is_final_event(br#"{"final": true}"#));
+        assert!(!is_final_event(br#"{"final": false, "status": "PENDING"}"#));
+        assert!(!is_final_event(br#"{"answer": "partial"}"#));
+        assert!(!is_final_event(b"not json"));
+    }
+
+    #[test]
+    fn test_parse_event_with_malformed_fields() {
+        let json = r#"{
+            "answer": "Still here",
+            "backend_uuid": 42,
+            "attachments": ["url1", null, {"url": "url2"}, "url3"],
+            "display_model": ["turbo"],
+            "status": {"code": 1},
+            "final_sse_message": "yes",
+            "final": true
+        }"#;
+
+        for keep_raw in [true, false] {
+            let event = parse_sse_event(json, keep_raw).unwrap();
+            assert_eq!(event.answer.as_deref(), Some("Still here"));
+            assert!(event.backend_uuid.is_none());
+            assert_eq!(event.attachments, vec!["url1", "url3"]);
+            assert!(event.display_model.is_none());
+            assert!(event.status.is_none());
+            assert!(event.is_final);
+        }
+
+        let event = parse_sse_event(r#"{"attachments": "url1"}"#, false).unwrap();
+        assert!(event.attachments.is_empty());
+    }
+
     #[test]
     fn test_parse_event_empty_fields() {
         let json = r#"{}"#;
-        let event = parse_sse_event(json).unwrap();
+        let event = parse_sse_event(json, true).unwrap();
 
         assert!(event.answer.is_none());
         assert!(event.web_results.is_empty());
         assert!(event.backend_uuid.is_none());
         assert!(event.attachments.is_empty());
+        assert!(event.display_model.is_none());
+        assert!(!event.is_final);
+    }
+
+    #[test]
+    fn test_parse_event_without_raw_matches_raw() {
+        let inner_answer = r#"{"answer": "Nested answer", "web_results": [{"name": "Source", "url": "https://example.com", "snippet": "Example"}]}"#;
+        let steps = serde_json::json!([
+            { "step_type": "SEARCH", "content": { "queries": ["rust"] } },
+            { "step_type": "FINAL", "content": { "answer": inner_answer } }
+        ]);
+        let payloads = [
+            serde_json::json!({
+                "text": serde_json::to_string(&steps).unwrap(),
+                "backend_uuid": "uuid",
+                "attachments": ["url1"],
+                "status": "COMPLETED",
+                "final": true
+            }),
+            serde_json::json!({ "text": steps, "attachments": null }),
+        ];
+
+        for payload in payloads {
+            let json = payload.to_string();
+            let with_raw = parse_sse_event(&json, true).unwrap();
+            let without_raw = parse_sse_event(&json, false).unwrap();
+
+            assert_eq!(without_raw.answer, Some("Nested answer".to_string()));
+            assert_eq!(without_raw.answer, with_raw.answer);
+            assert_eq!(without_raw.web_results.len(), with_raw.web_results.len());
+            assert_eq!(without_raw.backend_uuid, with_raw.backend_uuid);
+            assert_eq!(without_raw.attachments, with_raw.attachments);
+            assert_eq!(without_raw.status, with_raw.status);
+            assert_eq!(without_raw.is_final, with_raw.is_final);
+            assert!(without_raw.raw.is_null());
+            assert!(with_raw.raw.get("text").is_some_and(Value::is_array));
+        }
+    }
+
+    #[test]
+    fn test_parse_processing_events() {
+        let pending =
+            parse_processing_event(br#"{"file_uuid": "f1", "status": "PROCESSING"}"#).unwrap();
+        assert!(!pending.is_terminal());
+        assert!(pending.failure_reason().is_none());
+
+        let done = parse_processing_event(
+            br#"{"file_uuid": "f1", "status": "SUCCESS", "num_pages": 3, "char_count": 1200, "truncated": true}"#,
+        )
+        .unwrap();
+        assert!(done.is_terminal());
+        assert!(done.failure_reason().is_none());
+        assert_eq!(done.page_count, Some(3));
+        assert_eq!(done.char_count, Some(1200));
+        assert_eq!(done.truncated, Some(true));
+
+        let failed = parse_processing_event(
+            br#"{"file_uuid": "f1", "status": "FAILED", "error_message": "Unsupported file type"}"#,
+        )
+        .unwrap();
+        assert!(failed.is_terminal());
+        assert_eq!(failed.failure_reason().as_deref(), Some("Unsupported file type"));
+
+        let rejected =
+            parse_processing_event(br#"{"file_uuid": "f1", "status": "rejected"}"#).unwrap();
+        assert_eq!(rejected.failure_reason().as_deref(), Some("rejected"));
     }
 
     #[test]
     fn test_parse_invalid_json() {
-        let result = parse_sse_event("not json");
-        assert!(result.is_err());
+        assert!(parse_sse_event("not json", true).is_err());
+        assert!(parse_sse_event("not json", false).is_err());
     }
 }
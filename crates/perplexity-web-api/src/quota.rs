@@ -0,0 +1,130 @@
+use crate::config::{API_BASE_URL, API_VERSION, ENDPOINT_USER_SETTINGS};
+use crate::error::{Error, Result};
+use crate::rest::send;
+use crate::types::{Quota, SearchMode, Usage};
+use rquest::Client as HttpClient;
+use serde_json::Value;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Setting names of the Pro, reasoning and research allowances, in order of
+/// preference.
+const PRO_KEYS: &[&str] = &["gpt4_limit", "remaining_pro"];
+const REASONING_KEYS: &[&str] = &["o1_limit", "remaining_reasoning"];
+const RESEARCH_KEYS: &[&str] = &["pplx_alpha_limit", "remaining_research"];
+
+/// Fetches the remaining query allowances from the user settings.
+pub(crate) async fn fetch_quota(http: &HttpClient, timeout: Duration) -> Result<Quota> {
+    let request = http
+        .get(format!("{API_BASE_URL}{ENDPOINT_USER_SETTINGS}"))
+        .query(&[("version", API_VERSION), ("source", "default")]);
+
+    let settings: Value = send(request, timeout, Error::QuotaRequest)
+        .await?
+        .json()
+        .await
+        .map_err(Error::QuotaRequest)?;
+    Ok(quota_from_settings(&settings))
+}
+
+/// Reads the allowances from the user settings.
+///
+/// Each allowance is taken from the first of its names holding a count, so
+/// settings that report one under several names, or with another type, still
+/// parse.
+fn quota_from_settings(settings: &Value) -> Quota {
+    let remaining = |keys: &[&str]| {
+        keys.iter().find_map(|key| settings.get(key)?.as_u64()?.try_into().ok())
+    };
+    Quota {
+        pro_remaining: remaining(PRO_KEYS),
+        reasoning_remaining: remaining(REASONING_KEYS),
+        research_remaining: remaining(RESEARCH_KEYS),
+    }
+}
+
+/// Counts the queries sent per search mode, shared by all clones of a client.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct UsageCounter {
+    counts: Arc<[AtomicU64; 4]>,
+}
+
+impl UsageCounter {
+    pub(crate) fn record(&self, mode: SearchMode) {
+        self.counts[Self::slot(mode)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Usage {
+        let count = |mode| self.counts[Self::slot(mode)].load(Ordering::Relaxed);
+        Usage {
+            auto: count(SearchMode::Auto),
+            pro: count(SearchMode::Pro),
+            reasoning: count(SearchMode::Reasoning),
+            deep_research: count(SearchMode::DeepResearch),
+        }
+    }
+
+    fn slot(mode: SearchMode) -> usize {
+        match mode {
+            SearchMode::Auto => 0,
+            SearchMode::Pro => 1,
+            SearchMode::Reasoning => 2,
+            SearchMode::DeepResearch => 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_user_settings() {
+        let quota = quota_from_settings(&serde_json::json!({
+            "gpt4_limit": 297, "pplx_alpha_limit": 0, "o1_limit": 12, "theme": "dark"
+        }));
+
+        assert_eq!(quota.remaining(SearchMode::Auto), None);
+        assert_eq!(quota.remaining(SearchMode::Pro), Some(297));
+        assert_eq!(quota.remaining(SearchMode::Reasoning), Some(12));
+        assert_eq!(quota.remaining(SearchMode::DeepResearch), Some(0));
+        assert!(quota.is_exhausted(SearchMode::DeepResearch));
+        assert!(!quota.is_exhausted(SearchMode::Pro));
+    }
+
+    #[test]
+    fn reasoning_falls_back_to_pro_quota() {
+        let quota = quota_from_settings(&serde_json::json!({ "remaining_pro": 5 }));
+        assert_eq!(quota.remaining(SearchMode::Reasoning), Some(5));
+        assert_eq!(quota.remaining(SearchMode::DeepResearch), None);
+    }
+
+    #[test]
+    fn reads_allowances_reported_under_several_names() {
+        let quota = quota_from_settings(&serde_json::json!({
+            "gpt4_limit": 3,
+            "remaining_pro": 9,
+            "o1_limit": "unlimited",
+            "remaining_reasoning": 4,
+            "pplx_alpha_limit": -1,
+        }));
+        assert_eq!(quota.pro_remaining, Some(3));
+        assert_eq!(quota.reasoning_remaining, Some(4));
+        assert_eq!(quota.research_remaining, None);
+    }
+
+    #[test]
+    fn counts_usage_across_clones() {
+        let counter = UsageCounter::default();
+        let clone = counter.clone();
+        counter.record(SearchMode::Pro);
+        clone.record(SearchMode::Pro);
+        clone.record(SearchMode::DeepResearch);
+
+        let usage = counter.snapshot();
+        assert_eq!(usage.pro, 2);
+        assert_eq!(usage.deep_research, 1);
+        assert_eq!(usage.get(SearchMode::Auto), 0);
+    }
+}
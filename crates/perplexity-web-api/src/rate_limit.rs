@@ -0,0 +1,210 @@
+use crate::error::{Error, Result};
+use crate::types::SearchMode;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Token-bucket budget of search requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Requests allowed per minute once the burst is spent.
+    pub per_minute: u32,
+    /// Requests that may be sent back to back after a quiet period.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Creates a budget of `requests` per minute, all of which may be sent at once.
+    pub fn per_minute(requests: u32) -> Self {
+        Self { per_minute: requests, burst: requests }
+    }
+
+    /// Sets the number of requests that may be sent back to back.
+    pub fn burst(mut self, requests: u32) -> Self {
+        self.burst = requests;
+        self
+    }
+}
+
+/// Client-side rate limiter for search requests, shared by all its clones.
+///
+/// Each search mode draws from a token bucket set by
+/// [`mode_limit`](Self::mode_limit), [`shared_limit`](Self::shared_limit) or
+/// [`limit`](Self::limit), and modes without one are not limited.
+///
+/// Waiting callers are served in the order they arrived. A caller that gives
+/// up while waiting still spends its token.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    default_limit: Option<RateLimit>,
+    mode_limits: HashMap<SearchMode, (BucketKey, RateLimit)>,
+    groups: usize,
+    max_wait: Option<Duration>,
+    buckets: Arc<Mutex<HashMap<BucketKey, Bucket>>>,
+}
+
+/// Identifies the bucket a search mode draws from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum BucketKey {
+    Mode(SearchMode),
+    Group(usize),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Tokens available; negative while callers are queued.
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter without any budget.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the budget of every mode without a budget of its own.
+    pub fn limit(mut self, limit: RateLimit) -> Self {
+        self.default_limit = Some(limit);
+        self
+    }
+
+    /// Sets the budget of a single search mode.
+    pub fn mode_limit(mut self, mode: SearchMode, limit: RateLimit) -> Self {
+        self.mode_limits.insert(mode, (BucketKey::Mode(mode), limit));
+        self
+    }
+
+    /// Sets a single budget spent by searches in any of `modes`.
+    pub fn shared_limit(
+        mut self,
+        modes: impl IntoIterator<Item = SearchMode>,
+        limit: RateLimit,
+    ) -> Self {
+        let key = BucketKey::Group(self.groups);
+        self.groups += 1;
+        for mode in modes {
+            self.mode_limits.insert(mode, (key, limit));
+        }
+        self
+    }
+
+    /// Sets how long a request may wait for its turn before failing with
+    /// [`Error::RateLimited`]. Requests wait as long as needed by default.
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    /// Waits until a request in `mode` may be sent.
+    pub(crate) async fn acquire(&self, mode: SearchMode) -> Result<()> {
+        let wait = self.reserve(mode, Instant::now())?;
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    /// Takes a token of the mode's bucket, returning how long to wait for it.
+    fn reserve(&self, mode: SearchMode, now: Instant) -> Result<Duration> {
+        let Some((key, limit)) = self
+            .mode_limits
+            .get(&mode)
+            .copied()
+            .or_else(|| self.default_limit.map(|limit| (BucketKey::Mode(mode), limit)))
+        else {
+            return Ok(Duration::ZERO);
+        };
+        if limit.per_minute == 0 {
+            return Ok(Duration::ZERO);
+        }
+        let rate = f64::from(limit.per_minute) / 60.0;
+        let burst = f64::from(limit.burst.max(1));
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let bucket = buckets.entry(key).or_insert(Bucket { tokens: burst, updated: now });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = bucket.updated.max(now);
+
+        let tokens = bucket.tokens - 1.0;
+        let wait = if tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-tokens / rate)
+        };
+        if let Some(max_wait) = self.max_wait
+            && wait > max_wait
+        {
+            return Err(Error::RateLimited { mode, wait });
+        }
+        bucket.tokens = tokens;
+        Ok(wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queues_requests_beyond_burst() {
+        let limiter = RateLimiter::new().limit(RateLimit::per_minute(30).burst(2));
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve(SearchMode::Pro, now).unwrap(), Duration::ZERO);
+        assert_eq!(limiter.reserve(SearchMode::Pro, now).unwrap(), Duration::ZERO);
+        assert_eq!(limiter.reserve(SearchMode::Pro, now).unwrap(), Duration::from_secs(2));
+        assert_eq!(limiter.reserve(SearchMode::Pro, now).unwrap(), Duration::from_secs(4));
+
+        let later = now + Duration::from_secs(60);
+        assert_eq!(limiter.reserve(SearchMode::Pro, later).unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn keeps_separate_budgets_per_mode() {
+        let limiter = RateLimiter::new()
+            .mode_limit(SearchMode::DeepResearch, RateLimit::per_minute(1))
+            .max_wait(Duration::from_secs(10));
+        let now = Instant::now();
+
+        assert!(limiter.reserve(SearchMode::DeepResearch, now).is_ok());
+        assert!(matches!(
+            limiter.reserve(SearchMode::DeepResearch, now),
+            Err(Error::RateLimited { mode: SearchMode::DeepResearch, .. })
+        ));
+        for _ in 0..100 {
+            assert_eq!(limiter.reserve(SearchMode::Auto, now).unwrap(), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn shared_limit_spends_one_budget() {
+        let limiter = RateLimiter::new()
+            .shared_limit(
+                [SearchMode::Pro, SearchMode::Reasoning],
+                RateLimit::per_minute(60).burst(2),
+            )
+            .mode_limit(SearchMode::DeepResearch, RateLimit::per_minute(60).burst(1));
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve(SearchMode::Pro, now).unwrap(), Duration::ZERO);
+        assert_eq!(limiter.reserve(SearchMode::Reasoning, now).unwrap(), Duration::ZERO);
+        assert_eq!(
+            limiter.reserve(SearchMode::Reasoning, now).unwrap(),
+            Duration::from_secs(1)
+        );
+        assert_eq!(limiter.reserve(SearchMode::DeepResearch, now).unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn clones_share_buckets() {
+        let limiter = RateLimiter::new().limit(RateLimit::per_minute(60).burst(1));
+        let clone = limiter.clone();
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve(SearchMode::Auto, now).unwrap(), Duration::ZERO);
+        assert_eq!(clone.reserve(SearchMode::Auto, now).unwrap(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,446 @@
+use crate::disk_cache;
+use crate::models::ModelPreference;
+use crate::types::{
+    FollowUpContext, ResponseMetadata, ResponseTimings, SearchRequest, SearchResponse,
+    SearchWebResult,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Shortest time between two sweeps of expired entries of the disk backend.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Cache of search responses keyed by the account, the normalized query and
+/// the options that shape the answer: mode, models, sources, language, Space
+/// and incognito.
+///
+/// Only [`Client::search`](crate::Client::search) and the calls built on it
+/// use the cache; streaming searches never do. Requests with files, attachment
+/// URLs or follow-up context bypass it. Clones share the same entries and
+/// statistics, and clients signed in to different accounts never see each
+/// other's responses.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    backend: Arc<Backend>,
+    ttl: Duration,
+    stats: Arc<Counters>,
+    account: Arc<str>,
+}
+
+#[derive(Debug)]
+enum Backend {
+    Memory { capacity: usize, entries: Mutex<Lru> },
+    Disk { dir: PathBuf, last_sweep: Mutex<Option<Instant>> },
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Hit and miss counts of a [`ResponseCache`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Searches answered from the cache.
+    pub hits: u64,
+    /// Cacheable searches sent to Perplexity.
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Returns the share of cacheable searches answered from the cache.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let rate = self.hits as f64 / total as f64;
+        rate
+    }
+}
+
+/// Entries of the in-memory backend with their last use.
+#[derive(Debug, Default)]
+struct Lru {
+    entries: HashMap<String, (CachedResponse, u64)>,
+    clock: u64,
+}
+
+/// A cached response, stored as JSON by the disk backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    answer: Option<String>,
+    web_results: Vec<SearchWebResult>,
+    follow_up: FollowUpContext,
+    model_preference: ModelPreference,
+    display_model: Option<String>,
+    mode: Option<String>,
+    thread_url_slug: Option<String>,
+    status: Option<String>,
+    is_final: bool,
+    frontend_uuid: Option<String>,
+    raw: serde_json::Value,
+    stored_at: SystemTime,
+}
+
+impl ResponseCache {
+    /// Creates a cache kept in memory, holding up to `capacity` responses and
+    /// evicting the least recently used one when full.
+    pub fn in_memory(capacity: usize, ttl: Duration) -> Self {
+        Self::with_backend(Backend::Memory { capacity, entries: Mutex::default() }, ttl)
+    }
+
+    /// Creates a cache stored as one JSON file per response in `dir`,
+    /// shared between processes and restarts.
+    ///
+    /// The directory is created on the first insert. Failures to read or
+    /// write it are treated as cache misses. Inserts also remove expired
+    /// entries from the directory, at most every ten minutes; files the cache
+    /// did not write are left alone.
+    pub fn on_disk(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self::with_backend(
+            Backend::Disk { dir: dir.into(), last_sweep: Mutex::default() },
+            ttl,
+        )
+    }
+
+    fn with_backend(backend: Backend, ttl: Duration) -> Self {
+        Self { backend: Arc::new(backend), ttl, stats: Arc::default(), account: "".into() }
+    }
+
+    /// Scopes the cache to the account identified by `account`.
+    pub(crate) fn for_account(mut self, account: &str) -> Self {
+        self.account = account.into();
+        self
+    }
+
+    /// Returns how long responses are reused.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns the hit and miss counts since the cache was created.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Removes all entries. Other files in the cache directory are kept.
+    pub async fn clear(&self) -> std::io::Result<()> {
+        match &*self.backend {
+            Backend::Memory { entries, .. } => {
+                entries.lock().unwrap_or_else(PoisonError::into_inner).entries.clear();
+                Ok(())
+            }
+            Backend::Disk { dir, .. } => disk_cache::remove_files(dir, None).await,
+        }
+    }
+
+    /// Returns the cached response stored under `key`, unless expired,
+    /// counting the lookup as a hit or miss.
+    pub(crate) async fn get(&self, key: &str) -> Option<SearchResponse> {
+        let cached = self.lookup(key).await;
+        let counter = if cached.is_some() { &self.stats.hits } else { &self.stats.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        let cached = cached?;
+        let backend_uuid = cached.follow_up.backend_uuid.clone();
+        Some(SearchResponse {
+            answer: cached.answer,
+            web_results: cached.web_results,
+            follow_up: cached.follow_up,
+            model_preference: cached.model_preference,
+            metadata: ResponseMetadata {
+                display_model: cached.display_model,
+                mode: cached.mode,
+                thread_url_slug: cached.thread_url_slug,
+                status: cached.status,
+                is_final: cached.is_final,
+                frontend_uuid: cached.frontend_uuid,
+                backend_uuid,
+                timings: ResponseTimings::default(),
+            },
+            raw: cached.raw,
+        })
+    }
+
+    async fn lookup(&self, key: &str) -> Option<CachedResponse> {
+        let cached = match &*self.backend {
+            Backend::Memory { entries, .. } => {
+                let mut lru = entries.lock().unwrap_or_else(PoisonError::into_inner);
+                lru.clock += 1;
+                let clock = lru.clock;
+                let (cached, used) = lru.entries.get_mut(key)?;
+                *used = clock;
+                cached.clone()
+            }
+            Backend::Disk { dir, .. } => {
+                let json = tokio::fs::read(dir.join(format!("{key}.json"))).await.ok()?;
+                serde_json::from_slice(&json).ok()?
+            }
+        };
+        if cached.stored_at.elapsed().is_ok_and(|age| age > self.ttl) {
+            self.remove(key).await;
+            return None;
+        }
+        Some(cached)
+    }
+
+    /// Stores a response that has an answer.
+    pub(crate) async fn insert(&self, key: String, response: &SearchResponse) {
+        if response.answer.is_none() {
+            return;
+        }
+        let metadata = &response.metadata;
+        let cached = CachedResponse {
+            answer: response.answer.clone(),
+            web_results: response.web_results.clone(),
+            follow_up: response.follow_up.clone(),
+            model_preference: response.model_preference,
+            display_model: metadata.display_model.clone(),
+            mode: metadata.mode.clone(),
+            thread_url_slug: metadata.thread_url_slug.clone(),
+            status: metadata.status.clone(),
+            is_final: metadata.is_final,
+            frontend_uuid: metadata.frontend_uuid.clone(),
+            raw: response.raw.clone(),
+            stored_at: SystemTime::now(),
+        };
+        match &*self.backend {
+            Backend::Memory { capacity, entries } => {
+                if *capacity == 0 {
+                    return;
+                }
+                let mut lru = entries.lock().unwrap_or_else(PoisonError::into_inner);
+                if lru.entries.len() >= *capacity
+                    && !lru.entries.contains_key(&key)
+                    && let Some(oldest) = lru
+                        .entries
+                        .iter()
+                        .min_by_key(|(_, (_, used))| *used)
+                        .map(|(key, _)| key.clone())
+                {
+                    lru.entries.remove(&oldest);
+                }
+                lru.clock += 1;
+                let clock = lru.clock;
+                lru.entries.insert(key, (cached, clock));
+            }
+            Backend::Disk { dir, last_sweep } => {
+                let sweep_due = {
+                    let mut last_sweep =
+                        last_sweep.lock().unwrap_or_else(PoisonError::into_inner);
+                    let due = last_sweep.is_none_or(|at| at.elapsed() >= SWEEP_INTERVAL);
+                    if due {
+                        *last_sweep = Some(Instant::now());
+                    }
+                    due
+                };
+                if sweep_due {
+                    let _ = disk_cache::remove_files(dir, Some(self.ttl)).await;
+                }
+
+                let Ok(json) = serde_json::to_vec(&cached) else { return };
+                // Write to a temporary file first so readers never see a partial entry.
+                let tmp = dir.join(format!("{key}.{}.tmp", uuid::Uuid::new_v4()));
+                let stored = async {
+                    tokio::fs::create_dir_all(dir).await?;
+                    tokio::fs::write(&tmp, json).await?;
+                    tokio::fs::rename(&tmp, dir.join(format!("{key}.json"))).await
+                };
+                if stored.await.is_err() {
+                    let _ = tokio::fs::remove_file(&tmp).await;
+                }
+            }
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        match &*self.backend {
+            Backend::Memory { entries, .. } => {
+                entries.lock().unwrap_or_else(PoisonError::into_inner).entries.remove(key);
+            }
+            Backend::Disk { dir, .. } => {
+                let _ = tokio::fs::remove_file(dir.join(format!("{key}.json"))).await;
+            }
+        }
+    }
+
+    /// Returns the cache key of a request: a SHA-256 over the account, and
+    /// the request's normalized query, mode, models, sources, language, Space
+    /// and incognito flag.
+    ///
+    /// Returns `None` for requests with files, attachment URLs or follow-up
+    /// context, whose answers depend on more than the key covers.
+    pub(crate) fn key(&self, request: &SearchRequest) -> Option<String> {
+        if !request.files.is_empty()
+            || !request.attachment_urls.is_empty()
+            || request.follow_up.is_some()
+        {
+            return None;
+        }
+
+        let query =
+            request.query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        let mut sources: Vec<&str> = request.sources.iter().map(|s| s.as_str()).collect();
+        sources.sort_unstable();
+        sources.dedup();
+        let models: Vec<&str> = std::iter::once(request.primary_model())
+            .chain(request.fallback_models.iter().copied())
+            .map(|model| model.as_str())
+            .collect();
+
+        let mode = format!("{:?}", request.mode);
+
+        let mut hasher = Sha256::new();
+        for part in [
+            &*self.account,
+            query.as_str(),
+            &mode,
+            &models.join(","),
+            &sources.join(","),
+            &request.language,
+            request.space.as_deref().unwrap_or_default(),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update([u8::from(request.incognito)]);
+        Some(format!("{:x}", hasher.finalize()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SearchMode, SearchModel, Source, UploadFile};
+
+    fn response(answer: &str) -> SearchResponse {
+        SearchResponse {
+            answer: Some(answer.to_string()),
+            web_results: Vec::new(),
+            follow_up: FollowUpContext::default(),
+            model_preference: SearchModel::Turbo.into(),
+            metadata: ResponseMetadata::default(),
+            raw: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn keys_normalize_query_and_sources() {
+        let cache = ResponseCache::in_memory(1, Duration::from_secs(60));
+        let key = |request: SearchRequest| cache.key(&request);
+        let base =
+            key(SearchRequest::new("What is Rust?")
+                .sources(vec![Source::Web, Source::Scholar]));
+        assert!(base.is_some());
+        assert_eq!(
+            base,
+            key(SearchRequest::new("  what   is RUST? ")
+                .sources(vec![Source::Scholar, Source::Web]))
+        );
+        assert_ne!(
+            base,
+            key(SearchRequest::new("What is Rust?")
+                .sources(vec![Source::Web, Source::Scholar])
+                .mode(SearchMode::Pro))
+        );
+        assert_ne!(
+            base,
+            key(SearchRequest::new("What is Rust?")
+                .sources(vec![Source::Web, Source::Scholar])
+                .language("de-DE"))
+        );
+        assert_ne!(
+            base,
+            key(SearchRequest::new("What is Rust?")
+                .sources(vec![Source::Web, Source::Scholar])
+                .incognito(true))
+        );
+        let other_account = cache.clone().for_account("other");
+        assert_ne!(
+            base,
+            other_account.key(
+                &SearchRequest::new("What is Rust?")
+                    .sources(vec![Source::Web, Source::Scholar])
+            )
+        );
+    }
+
+    #[test]
+    fn bypasses_files_and_follow_ups() {
+        let cache = ResponseCache::in_memory(1, Duration::from_secs(60));
+        let with_file = SearchRequest::new("q").file(UploadFile::from_text("a.txt", "a"));
+        assert!(cache.key(&with_file).is_none());
+        let follow_up = SearchRequest::new("q").follow_up(FollowUpContext::default());
+        assert!(cache.key(&follow_up).is_none());
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let cache = ResponseCache::in_memory(2, Duration::from_secs(60));
+        cache.insert("a".to_string(), &response("A")).await;
+        cache.insert("b".to_string(), &response("B")).await;
+        assert!(cache.get("a").await.is_some());
+        cache.insert("c".to_string(), &response("C")).await;
+
+        assert!(cache.get("b").await.is_none());
+        assert_eq!(cache.get("a").await.unwrap().answer.as_deref(), Some("A"));
+        assert_eq!(cache.get("c").await.unwrap().answer.as_deref(), Some("C"));
+        assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 1 });
+    }
+
+    #[tokio::test]
+    async fn persists_entries_on_disk_until_expired() {
+        let dir =
+            std::env::temp_dir().join(format!("response-cache-{}", uuid::Uuid::new_v4()));
+        let cache = ResponseCache::on_disk(&dir, Duration::from_secs(60));
+        let key = cache.key(&SearchRequest::new("q")).unwrap();
+        cache.insert(key.clone(), &response("cached")).await;
+
+        let reopened = ResponseCache::on_disk(&dir, Duration::from_secs(60));
+        assert_eq!(reopened.get(&key).await.unwrap().answer.as_deref(), Some("cached"));
+
+        let expired = ResponseCache::on_disk(&dir, Duration::ZERO);
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(expired.get(&key).await.is_none());
+        reopened.clear().await.unwrap();
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sweeps_expired_files_on_insert() {
+        let dir =
+            std::env::temp_dir().join(format!("response-cache-{}", uuid::Uuid::new_v4()));
+        let cache = ResponseCache::on_disk(&dir, Duration::from_secs(60));
+        let old = cache.key(&SearchRequest::new("old")).unwrap();
+        let new = cache.key(&SearchRequest::new("new")).unwrap();
+        cache.insert(old, &response("old")).await;
+        std::fs::write(dir.join(format!("{new}.{}.tmp", uuid::Uuid::new_v4())), b"partial")
+            .unwrap();
+        std::fs::write(dir.join("notes.txt"), b"not ours").unwrap();
+
+        let short_lived = ResponseCache::on_disk(&dir, Duration::from_millis(1));
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        short_lived.insert(new.clone(), &response("new")).await;
+
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|file| file.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec![format!("{new}.json"), "notes.txt".to_string()]);
+        cache.clear().await.unwrap();
+        std::fs::remove_file(dir.join("notes.txt")).unwrap();
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
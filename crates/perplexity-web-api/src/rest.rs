@@ -0,0 +1,23 @@
+use crate::config::API_REFERER;
+use crate::error::{Error, Result};
+use rquest::header::REFERER;
+use rquest::{RequestBuilder, Response};
+use std::time::Duration;
+
+/// Sends a request to a REST endpoint of the web app, reporting transport
+/// errors with `map_err` and error statuses as [`Error::Server`].
+pub(crate) async fn send(
+    request: RequestBuilder,
+    timeout: Duration,
+    map_err: fn(rquest::Error) -> Error,
+) -> Result<Response> {
+    tokio::time::timeout(timeout, request.header(REFERER, API_REFERER).send())
+        .await
+        .map_err(|_| Error::Timeout(timeout))?
+        .map_err(map_err)?
+        .error_for_status()
+        .map_err(|e| Error::Server {
+            status: e.status().map(|s| s.as_u16()).unwrap_or(0),
+            message: e.to_string(),
+        })
+}
@@ -0,0 +1,139 @@
+/// Number of leading bytes inspected to detect the content type of a file.
+pub(crate) const SNIFF_LEN: usize = 512;
+
+/// Content type detected from the leading bytes of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sniffed {
+    /// A signature specific enough to override the filename extension.
+    Exact(&'static str),
+    /// A generic container or plain text, used only when the filename says nothing.
+    Generic(&'static str),
+}
+
+/// Magic-byte signatures matched at the start of a file.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"II*\0", "image/tiff"),
+    (b"MM\0*", "image/tiff"),
+];
+
+/// Returns the MIME type of a file from its name and leading bytes.
+///
+/// Binary signatures take precedence over the extension, so screenshots
+/// named `image` or PDFs saved as `.txt` are sent with their real type.
+/// Zip archives and plain text only fill in for a missing or unknown
+/// extension, since formats like `.docx` or `.csv` are more specific.
+pub(crate) fn detect_content_type(filename: &str, head: Option<&[u8]>) -> String {
+    let guessed = mime_guess::from_path(filename).first();
+    match (guessed, head.and_then(sniff)) {
+        (_, Some(Sniffed::Exact(mime))) => mime.to_string(),
+        (Some(guessed), _) => guessed.to_string(),
+        (None, Some(Sniffed::Generic(mime))) => mime.to_string(),
+        (None, None) => mime_guess::mime::APPLICATION_OCTET_STREAM.to_string(),
+    }
+}
+
+fn sniff(head: &[u8]) -> Option<Sniffed> {
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return Some(Sniffed::Exact(mime));
+    }
+    if head.len() >= 12 && head.starts_with(b"RIFF") && &head[8..12] == b"WEBP" {
+        return Some(Sniffed::Exact("image/webp"));
+    }
+    if is_bmp(head) {
+        return Some(Sniffed::Exact("image/bmp"));
+    }
+    if head.starts_with(b"PK\x03\x04") {
+        return Some(Sniffed::Generic("application/zip"));
+    }
+    if !head.is_empty() && is_text(head) {
+        return Some(Sniffed::Generic("text/plain"));
+    }
+    None
+}
+
+/// Whether `head` starts with a bitmap file header followed by a known DIB
+/// header, since `BM` alone also starts plenty of text.
+fn is_bmp(head: &[u8]) -> bool {
+    let u32_at =
+        |at: usize| u32::from_le_bytes([head[at], head[at + 1], head[at + 2], head[at + 3]]);
+    if head.len() < 18 || !head.starts_with(b"BM") || head[6..10] != [0; 4] {
+        return false;
+    }
+    let (file_size, pixel_offset, dib_size) = (u32_at(2), u32_at(10), u32_at(14));
+    matches!(dib_size, 12 | 40 | 52 | 56 | 64 | 108 | 124)
+        && pixel_offset >= 14 + dib_size
+        && file_size >= pixel_offset
+}
+
+/// Whether `head` looks like UTF-8 text, allowing a character cut off at the end.
+fn is_text(head: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+    valid && !head.contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_type_of_file_without_extension() {
+        assert_eq!(detect_content_type("image", Some(b"\x89PNG\r\n\x1a\n\0\0")), "image/png");
+        assert_eq!(detect_content_type("export", Some(b"%PDF-1.7\n")), "application/pdf");
+        assert_eq!(detect_content_type("notes", Some("héllo".as_bytes())), "text/plain");
+        assert_eq!(
+            detect_content_type("blob", Some(b"\0\x01\x02")),
+            "application/octet-stream"
+        );
+        assert_eq!(detect_content_type("stream", None), "application/octet-stream");
+    }
+
+    #[test]
+    fn signature_overrides_wrong_extension() {
+        assert_eq!(detect_content_type("scan.txt", Some(b"%PDF-1.4")), "application/pdf");
+        assert_eq!(
+            detect_content_type("photo.png", Some(b"RIFF\0\0\0\0WEBPVP8 ")),
+            "image/webp"
+        );
+    }
+
+    #[test]
+    fn checks_bitmap_header() {
+        let mut bmp = b"BM".to_vec();
+        bmp.extend_from_slice(&70u32.to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&54u32.to_le_bytes());
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        assert_eq!(detect_content_type("image", Some(&bmp)), "image/bmp");
+
+        assert_eq!(
+            detect_content_type("notes.md", Some(b"BMW unveiled a new car")),
+            "text/markdown"
+        );
+        assert_eq!(detect_content_type("car", Some(b"BMW unveiled a new car")), "text/plain");
+        bmp[6] = 1;
+        assert_eq!(detect_content_type("blob", Some(&bmp)), "application/octet-stream");
+    }
+
+    #[test]
+    fn extension_wins_over_generic_contents() {
+        let docx = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+        assert_eq!(detect_content_type("report.docx", Some(b"PK\x03\x04")), docx);
+        assert_eq!(detect_content_type("data.csv", Some(b"a,b\n1,2\n")), "text/csv");
+        assert_eq!(detect_content_type("archive", Some(b"PK\x03\x04")), "application/zip");
+    }
+
+    #[test]
+    fn accepts_text_cut_mid_character() {
+        let text = "né".as_bytes();
+        assert!(is_text(&text[..text.len() - 1]));
+        assert!(!is_text(b"\xff\xfe"));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::config::{
+    API_BASE_URL, API_VERSION, ENDPOINT_SPACE, ENDPOINT_SPACE_FILES, ENDPOINT_SPACE_LIST,
+};
+use crate::error::{Error, Result};
+use crate::rest::send;
+use crate::types::{Pagination, Space, SpaceFile};
+use rquest::Client as HttpClient;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A list returned either bare or wrapped in an object.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Listing<T> {
+    Bare(Vec<T>),
+    Spaces { collections: Vec<T> },
+    Files { files: Vec<T> },
+}
+
+impl<T> Listing<T> {
+    fn into_items(self) -> Vec<T> {
+        match self {
+            Self::Bare(items)
+            | Self::Spaces { collections: items }
+            | Self::Files { files: items } => items,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ListFilesRequest<'a> {
+    file_repository_info: FileRepositoryInfo<'a>,
+    limit: u32,
+    offset: u32,
+    search_term: &'static str,
+}
+
+#[derive(Serialize)]
+struct FileRepositoryInfo<'a> {
+    file_repository_type: &'static str,
+    owner_id: &'a str,
+}
+
+/// Lists the user's Spaces.
+pub(crate) async fn list_spaces(
+    http: &HttpClient,
+    timeout: Duration,
+    page: Pagination,
+) -> Result<Vec<Space>> {
+    let request = http.get(format!("{API_BASE_URL}{ENDPOINT_SPACE_LIST}")).query(&[
+        ("limit", page.limit.to_string().as_str()),
+        ("offset", page.offset.to_string().as_str()),
+        ("version", API_VERSION),
+        ("source", "default"),
+    ]);
+
+    let listing: Listing<Space> = send(request, timeout, Error::SpaceRequest)
+        .await?
+        .json()
+        .await
+        .map_err(Error::SpaceRequest)?;
+    Ok(listing.into_items())
+}
+
+/// Fetches a Space by its slug or UUID.
+pub(crate) async fn get_space(
+    http: &HttpClient,
+    timeout: Duration,
+    slug_or_uuid: &str,
+) -> Result<Space> {
+    let key = if Uuid::parse_str(slug_or_uuid).is_ok() {
+        "collection_uuid"
+    } else {
+        "collection_slug"
+    };
+    let request = http.get(format!("{API_BASE_URL}{ENDPOINT_SPACE}")).query(&[
+        (key, slug_or_uuid),
+        ("version", API_VERSION),
+        ("source", "default"),
+    ]);
+
+    send(request, timeout, Error::SpaceRequest)
+        .await?
+        .json()
+        .await
+        .map_err(Error::SpaceRequest)
+}
+
+/// Lists the files attached to a Space.
+pub(crate) async fn list_space_files(
+    http: &HttpClient,
+    timeout: Duration,
+    uuid: &str,
+    page: Pagination,
+) -> Result<Vec<SpaceFile>> {
+    let body = ListFilesRequest {
+        file_repository_info: FileRepositoryInfo {
+            file_repository_type: "COLLECTION",
+            owner_id: uuid,
+        },
+        limit: page.limit,
+        offset: page.offset,
+        search_term: "",
+    };
+    let request = http
+        .post(format!("{API_BASE_URL}{ENDPOINT_SPACE_FILES}"))
+        .query(&[("version", API_VERSION), ("source", "default")])
+        .json(&body);
+
+    let listing: Listing<SpaceFile> = send(request, timeout, Error::SpaceRequest)
+        .await?
+        .json()
+        .await
+        .map_err(Error::SpaceRequest)?;
+    Ok(listing.into_items())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bare_and_wrapped_listings() {
+        let bare: Listing<Space> =
+            serde_json::from_str(r#"[{"uuid": "s1", "title": "Standards"}]"#).unwrap();
+        let spaces = bare.into_items();
+        assert_eq!(spaces[0].uuid, "s1");
+        assert_eq!(spaces[0].title, "Standards");
+
+        let wrapped: Listing<Space> = serde_json::from_str(
+            r#"{"collections": [{"uuid": "s2", "title": "Docs", "instructions": "Be brief"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(wrapped.into_items()[0].instructions.as_deref(), Some("Be brief"));
+    }
+
+    #[test]
+    fn parses_space_files() {
+        let listing: Listing<SpaceFile> = serde_json::from_str(
+            r#"{"files": [{"file_uuid": "f1", "file_name": "guide.pdf", "size": 1024}]}"#,
+        )
+        .unwrap();
+        let files = listing.into_items();
+        assert_eq!(files[0].uuid, "f1");
+        assert_eq!(files[0].filename, "guide.pdf");
+        assert_eq!(files[0].file_size, Some(1024));
+    }
+}
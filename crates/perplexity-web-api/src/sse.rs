@@ -1,5 +1,6 @@
 use crate::error::{Error, Result};
 use crate::parse::parse_sse_event;
+use crate::trace::StreamTrace;
 use crate::types::SearchEvent;
 use bytes::{Bytes, BytesMut};
 use futures_util::Stream;
@@ -16,28 +17,56 @@ static DELIMITER_FINDER: LazyLock<memmem::Finder<'static>> =
     LazyLock::new(|| memmem::Finder::new(DELIMITER));
 
 pin_project_lite::pin_project! {
-    pub struct SseStream<S> {
+    /// Splits a byte stream into the JSON payloads of `message` events
+    /// without parsing them.
+    pub struct SseFrames<S> {
         #[pin]
         inner: S,
         buffer: BytesMut,
         finished: bool,
+        all_events: bool,
+        map_err: fn(rquest::Error) -> Error,
+        trace: StreamTrace,
     }
 }
 
-impl<S> SseStream<S>
+impl<S> SseFrames<S>
 where
     S: Stream<Item = std::result::Result<Bytes, rquest::Error>>,
 {
     pub fn new(inner: S) -> Self {
-        Self { inner, buffer: BytesMut::new(), finished: false }
+        Self::with_error(inner, Error::SearchRequest)
+    }
+
+    /// Creates a frame stream that reports transport errors with `map_err`.
+    pub fn with_error(inner: S, map_err: fn(rquest::Error) -> Error) -> Self {
+        Self {
+            inner,
+            buffer: BytesMut::new(),
+            finished: false,
+            all_events: false,
+            map_err,
+            trace: StreamTrace::new(),
+        }
+    }
+
+    /// Yields the payloads of events of every name, not only `message`.
+    pub(crate) fn all_events(mut self) -> Self {
+        self.all_events = true;
+        self
+    }
+
+    /// Records that the consumer stopped reading after the final event.
+    pub(crate) fn finish_at_final_event(self: Pin<&mut Self>) {
+        self.project().trace.finish("final_event");
     }
 }
 
-impl<S> Stream for SseStream<S>
+impl<S> Stream for SseFrames<S>
 where
     S: Stream<Item = std::result::Result<Bytes, rquest::Error>>,
 {
-    type Item = Result<SearchEvent>;
+    type Item = Result<Bytes>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let mut this = self.project();
@@ -47,22 +76,28 @@ where
         }
 
         loop {
-            if let Some(event) = try_parse_event(this.buffer, this.finished) {
-                return Poll::Ready(Some(event));
+            if let Some(frame) = try_split_frame(this.buffer, this.finished, *this.all_events)
+            {
+                this.trace.event();
+                return Poll::Ready(Some(Ok(frame)));
             }
 
             if *this.finished {
+                this.trace.finish("end_of_stream");
                 return Poll::Ready(None);
             }
 
             match this.inner.as_mut().poll_next(cx) {
                 Poll::Ready(Some(Ok(chunk))) => {
+                    this.trace.chunk(chunk.len());
                     this.buffer.extend_from_slice(&chunk);
                 }
                 Poll::Ready(Some(Err(e))) => {
-                    return Poll::Ready(Some(Err(Error::SearchRequest(e))));
+                    this.trace.finish("error");
+                    return Poll::Ready(Some(Err((this.map_err)(e))));
                 }
                 Poll::Ready(None) => {
+                    this.trace.finish("eof");
                     *this.finished = true;
                     if this.buffer.is_empty() {
                         return Poll::Ready(None);
@@ -76,10 +111,56 @@ where
     }
 }
 
-#[allow(clippy::collapsible_if)]
-fn try_parse_event(buffer: &mut BytesMut, finished: &mut bool) -> Option<Result<SearchEvent>> {
-    if let Some(pos) = DELIMITER_FINDER.find(buffer) {
-        let event_bytes = buffer.split_to(pos + DELIMITER.len());
+pin_project_lite::pin_project! {
+    /// Parses every `message` event of a byte stream into a [`SearchEvent`].
+    pub struct SseStream<S> {
+        #[pin]
+        frames: SseFrames<S>,
+        keep_raw: bool,
+    }
+}
+
+impl<S> SseStream<S>
+where
+    S: Stream<Item = std::result::Result<Bytes, rquest::Error>>,
+{
+    pub fn new(inner: S, keep_raw: bool) -> Self {
+        Self { frames: SseFrames::new(inner), keep_raw }
+    }
+}
+
+impl<S> Stream for SseStream<S>
+where
+    S: Stream<Item = std::result::Result<Bytes, rquest::Error>>,
+{
+    type Item = Result<SearchEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let keep_raw = *this.keep_raw;
+        this.frames.poll_next(cx).map(|frame| {
+            frame.map(|frame| frame.and_then(|frame| parse_frame(&frame, keep_raw)))
+        })
+    }
+}
+
+/// Parses a `message` event payload into a [`SearchEvent`].
+pub(crate) fn parse_frame(frame: &[u8], keep_raw: bool) -> Result<SearchEvent> {
+    let json_str = std::str::from_utf8(frame).map_err(|_| Error::InvalidUtf8)?;
+    parse_sse_event(json_str, keep_raw)
+}
+
+/// Splits the next complete event off the buffer and returns its data payload.
+///
+/// Events other than `message` are skipped unless `all_events` is set;
+/// `end_of_stream` marks the stream as finished.
+fn try_split_frame(
+    buffer: &mut BytesMut,
+    finished: &mut bool,
+    all_events: bool,
+) -> Option<Bytes> {
+    while let Some(pos) = DELIMITER_FINDER.find(buffer) {
+        let event_bytes = buffer.split_to(pos + DELIMITER.len()).freeze();
         let event_data = &event_bytes[..pos];
 
         // Check for end of stream event
@@ -89,18 +170,54 @@ fn try_parse_event(buffer: &mut BytesMut, finished: &mut bool) -> Option<Result<
         }
 
         // Check for message event
-        if event_data.starts_with(EVENT_MESSAGE_PREFIX) {
-            let after_event = &event_data[EVENT_MESSAGE_PREFIX.len()..];
-            if let Some(data_start) = memmem::find(after_event, DATA_PREFIX) {
-                let json_bytes = &after_event[data_start + DATA_PREFIX.len()..];
-                // Validate UTF-8 and parse
-                return match std::str::from_utf8(json_bytes) {
-                    Ok(json_str) => Some(parse_sse_event(json_str)),
-                    Err(_) => Some(Err(Error::InvalidUtf8)),
-                };
-            }
+        if (all_events || event_data.starts_with(EVENT_MESSAGE_PREFIX))
+            && let Some(data_start) = memmem::find(event_data, DATA_PREFIX)
+        {
+            let json_start = data_start + DATA_PREFIX.len();
+            return Some(event_bytes.slice(json_start..pos));
         }
     }
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn chunks(parts: &[&'static str]) -> SseFrames<impl Stream<Item = rquest::Result<Bytes>>> {
+        let parts: Vec<rquest::Result<Bytes>> =
+            parts.iter().map(|part| Ok(Bytes::from_static(part.as_bytes()))).collect();
+        SseFrames::new(futures_util::stream::iter(parts))
+    }
+
+    #[tokio::test]
+    async fn test_frames_split_across_chunks() {
+        let frames: Vec<Bytes> = chunks(&[
+            "event: message\r\ndata: {\"a\"",
+            ": 1}\r\n\r\nevent: ping\r\ndata: {}\r\n\r\nevent: message\r\ndata: {\"b\": 2}\r\n\r\n",
+        ])
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+        assert_eq!(
+            frames,
+            vec![Bytes::from_static(b"{\"a\": 1}"), Bytes::from_static(b"{\"b\": 2}")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_frames_stop_at_end_of_stream() {
+        let frames: Vec<Bytes> = chunks(&[
+            "event: message\r\ndata: {}\r\n\r\n",
+            "event: end_of_stream\r\ndata: {}\r\n\r\nevent: message\r\ndata: {}\r\n\r\n",
+        ])
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+        assert_eq!(frames.len(), 1);
+    }
+}
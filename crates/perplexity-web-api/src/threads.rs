@@ -0,0 +1,260 @@
+use crate::config::{
+    API_BASE_URL, API_VERSION, ENDPOINT_THREAD, ENDPOINT_THREAD_DELETE, ENDPOINT_THREAD_LIST,
+};
+use crate::error::{Error, Result};
+use crate::parse::parse_sse_event;
+use crate::rest::send;
+use crate::types::{Pagination, Thread, ThreadEntry, ThreadSummary};
+use rquest::{Client as HttpClient, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Number of entries requested per page while fetching a thread.
+const THREAD_PAGE_SIZE: u32 = 100;
+
+/// Most pages fetched for one thread, in case the server keeps reporting a
+/// next page.
+const MAX_THREAD_PAGES: usize = 50;
+
+#[derive(Serialize)]
+struct ListThreadsRequest {
+    limit: u32,
+    offset: u32,
+    ascending: bool,
+    search_term: &'static str,
+}
+
+#[derive(Deserialize)]
+struct ThreadPageResponse {
+    #[serde(default)]
+    entries: Vec<Box<RawValue>>,
+    #[serde(default)]
+    has_next_page: bool,
+}
+
+/// Entry fields that are not part of a search event.
+#[derive(Deserialize)]
+struct EntryFields {
+    #[serde(default)]
+    query_str: String,
+    #[serde(default)]
+    read_write_token: Option<String>,
+    #[serde(default, alias = "entry_updated_datetime")]
+    updated_datetime: Option<String>,
+    #[serde(default)]
+    thread_url_slug: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DeleteThreadRequest<'a> {
+    entry_uuid: &'a str,
+    read_write_token: Option<&'a str>,
+}
+
+/// Lists threads of the user's library, newest first.
+pub(crate) async fn list_threads(
+    http: &HttpClient,
+    timeout: Duration,
+    page: Pagination,
+) -> Result<Vec<ThreadSummary>> {
+    let body = ListThreadsRequest {
+        limit: page.limit,
+        offset: page.offset,
+        ascending: false,
+        search_term: "",
+    };
+    let request = http
+        .post(format!("{API_BASE_URL}{ENDPOINT_THREAD_LIST}"))
+        .query(&[("version", API_VERSION), ("source", "default")])
+        .json(&body);
+
+    send(request, timeout, Error::ThreadRequest)
+        .await?
+        .json()
+        .await
+        .map_err(Error::ThreadRequest)
+}
+
+/// Fetches every entry of a thread, following the server's pagination.
+pub(crate) async fn get_thread(
+    http: &HttpClient,
+    timeout: Duration,
+    slug_or_uuid: &str,
+) -> Result<Thread> {
+    let mut url: Url = format!("{API_BASE_URL}{ENDPOINT_THREAD}")
+        .parse()
+        .map_err(|_| Error::InvalidBaseUrl)?;
+    url.path_segments_mut().map_err(|()| Error::InvalidBaseUrl)?.push(slug_or_uuid);
+
+    let mut slug = None;
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    for _ in 0..MAX_THREAD_PAGES {
+        let offset = entries.len().to_string();
+        let request = http.get(url.clone()).query(&[
+            ("with_schematized_response", "true"),
+            ("version", API_VERSION),
+            ("source", "default"),
+            ("limit", &THREAD_PAGE_SIZE.to_string()),
+            ("offset", &offset),
+            ("from_first", "true"),
+        ]);
+        let page: ThreadPageResponse = send(request, timeout, Error::ThreadRequest)
+            .await?
+            .json()
+            .await
+            .map_err(Error::ThreadRequest)?;
+
+        let mut parsed = Vec::with_capacity(page.entries.len());
+        for entry in page.entries {
+            let (entry, entry_slug) = parse_entry(entry.get())?;
+            slug = slug.or(entry_slug);
+            parsed.push(entry);
+        }
+        let has_new = append_new_entries(&mut entries, &mut seen, parsed);
+        if !page.has_next_page || !has_new {
+            break;
+        }
+    }
+
+    if entries.is_empty() {
+        return Err(Error::ThreadNotFound(slug_or_uuid.to_string()));
+    }
+    Ok(Thread { slug: slug.unwrap_or_else(|| slug_or_uuid.to_string()), entries })
+}
+
+/// Appends the entries not seen on an earlier page, returning whether any
+/// entry brought a new backend UUID.
+///
+/// Guards against a server that ignores the offset and returns the same page
+/// again.
+fn append_new_entries(
+    entries: &mut Vec<ThreadEntry>,
+    seen: &mut HashSet<String>,
+    page: Vec<ThreadEntry>,
+) -> bool {
+    let mut has_new = false;
+    for entry in page {
+        match &entry.backend_uuid {
+            Some(uuid) if !seen.insert(uuid.clone()) => continue,
+            Some(_) => has_new = true,
+            None => {}
+        }
+        entries.push(entry);
+    }
+    has_new
+}
+
+/// Deletes a thread from the user's library.
+pub(crate) async fn delete_thread(
+    http: &HttpClient,
+    timeout: Duration,
+    slug_or_uuid: &str,
+) -> Result<()> {
+    let thread = get_thread(http, timeout, slug_or_uuid).await?;
+    let entry = thread
+        .entries
+        .iter()
+        .find(|entry| entry.backend_uuid.is_some())
+        .ok_or_else(|| Error::ThreadNotFound(slug_or_uuid.to_string()))?;
+
+    let body = DeleteThreadRequest {
+        entry_uuid: entry.backend_uuid.as_deref().unwrap_or_default(),
+        read_write_token: entry.read_write_token.as_deref(),
+    };
+    let request = http
+        .delete(format!("{API_BASE_URL}{ENDPOINT_THREAD_DELETE}"))
+        .query(&[("version", API_VERSION), ("source", "default")])
+        .json(&body);
+
+    send(request, timeout, Error::ThreadRequest).await?;
+    Ok(())
+}
+
+/// Parses a thread entry, returning it with the thread slug it reports.
+fn parse_entry(json: &str) -> Result<(ThreadEntry, Option<String>)> {
+    let event = parse_sse_event(json, false)?;
+    let fields: EntryFields = serde_json::from_str(json)?;
+
+    let entry = ThreadEntry {
+        query: fields.query_str,
+        answer: event.answer,
+        web_results: event.web_results,
+        backend_uuid: event.backend_uuid,
+        attachments: event.attachments,
+        display_model: event.display_model,
+        mode: event.mode,
+        updated_datetime: fields.updated_datetime,
+        read_write_token: fields.read_write_token,
+    };
+    Ok((entry, fields.thread_url_slug.or(event.thread_url_slug)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_thread_entry() {
+        let answer = serde_json::json!({
+            "answer": "Rust was designed by Graydon Hoare.",
+            "web_results": [{"name": "Rust", "url": "https://rust-lang.org", "snippet": "Rust"}],
+        });
+        let json = serde_json::json!({
+            "query_str": "Who designed Rust?",
+            "backend_uuid": "entry-1",
+            "thread_url_slug": "who-designed-rust-abc",
+            "read_write_token": "token-1",
+            "updated_datetime": "2026-01-02T03:04:05Z",
+            "display_model": "turbo",
+            "text": serde_json::json!([
+                {"step_type": "FINAL", "content": {"answer": answer.to_string()}}
+            ])
+            .to_string(),
+        });
+
+        let (entry, slug) = parse_entry(&json.to_string()).unwrap();
+        assert_eq!(slug.as_deref(), Some("who-designed-rust-abc"));
+        assert_eq!(entry.query, "Who designed Rust?");
+        assert_eq!(entry.answer.as_deref(), Some("Rust was designed by Graydon Hoare."));
+        assert_eq!(entry.web_results.len(), 1);
+        assert_eq!(entry.backend_uuid.as_deref(), Some("entry-1"));
+        assert_eq!(entry.read_write_token.as_deref(), Some("token-1"));
+        assert_eq!(entry.updated_datetime.as_deref(), Some("2026-01-02T03:04:05Z"));
+    }
+
+    #[test]
+    fn skips_repeated_pages() {
+        let entry = |uuid: &str| ThreadEntry {
+            query: String::new(),
+            answer: None,
+            web_results: Vec::new(),
+            backend_uuid: Some(uuid.to_string()),
+            attachments: Vec::new(),
+            display_model: None,
+            mode: None,
+            updated_datetime: None,
+            read_write_token: None,
+        };
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+
+        assert!(append_new_entries(&mut entries, &mut seen, vec![entry("a"), entry("b")]));
+        assert!(!append_new_entries(&mut entries, &mut seen, vec![entry("a"), entry("b")]));
+        assert!(append_new_entries(&mut entries, &mut seen, vec![entry("b"), entry("c")]));
+        let uuids: Vec<_> = entries.iter().filter_map(|e| e.backend_uuid.as_deref()).collect();
+        assert_eq!(uuids, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn parses_thread_summaries() {
+        let json = r#"[{"slug": "abc", "title": "Rust", "query_count": 2, "extra": 1}, {}]"#;
+        let threads: Vec<ThreadSummary> = serde_json::from_str(json).unwrap();
+        assert_eq!(threads[0].slug, "abc");
+        assert_eq!(threads[0].query_count, Some(2));
+        assert_eq!(threads[0].url(), "https://www.perplexity.ai/search/abc");
+        assert!(threads[1].title.is_none());
+    }
+}
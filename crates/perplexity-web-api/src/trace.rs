@@ -0,0 +1,74 @@
+//! Lifecycle tracking of event streams, reported through `tracing` when the
+//! `tracing` feature is enabled and compiled out otherwise.
+
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Counts the events and bytes of a stream and reports them, along with why
+/// it ended, when the stream is dropped.
+#[cfg(feature = "tracing")]
+pub(crate) struct StreamTrace {
+    span: tracing::Span,
+    started: Instant,
+    events: u64,
+    bytes: u64,
+    termination: Option<&'static str>,
+}
+
+#[cfg(feature = "tracing")]
+impl StreamTrace {
+    /// Starts tracking a stream, in a span inside the current one.
+    pub(crate) fn new() -> Self {
+        Self {
+            span: tracing::debug_span!("stream"),
+            started: Instant::now(),
+            events: 0,
+            bytes: 0,
+            termination: None,
+        }
+    }
+
+    pub(crate) fn chunk(&mut self, len: usize) {
+        self.bytes += len as u64;
+    }
+
+    pub(crate) fn event(&mut self) {
+        self.events += 1;
+    }
+
+    /// Records why the stream ended. Only the first reason is kept.
+    pub(crate) fn finish(&mut self, reason: &'static str) {
+        self.termination.get_or_insert(reason);
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for StreamTrace {
+    fn drop(&mut self) {
+        tracing::debug!(
+            parent: &self.span,
+            events = self.events,
+            bytes = self.bytes,
+            elapsed_ms = self.started.elapsed().as_millis(),
+            termination = self.termination.unwrap_or("dropped"),
+            "stream closed"
+        );
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct StreamTrace;
+
+#[cfg(not(feature = "tracing"))]
+#[allow(clippy::unused_self)]
+impl StreamTrace {
+    pub(crate) fn new() -> Self {
+        Self
+    }
+
+    pub(crate) fn chunk(&mut self, _len: usize) {}
+
+    pub(crate) fn event(&mut self) {}
+
+    pub(crate) fn finish(&mut self, _reason: &'static str) {}
+}
@@ -1,13 +1,20 @@
+use crate::config::{API_BASE_URL, THREAD_PATH};
 use crate::models::{DEEP_RESEARCH_MODEL_PREFERENCE, ModelPreference};
 use crate::{ReasonModel, SearchModel};
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
+use std::path::PathBuf;
+use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::io::AsyncRead;
 
 /// Search mode for Perplexity queries.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SearchMode {
     /// Default mode using the turbo model.
     #[default]
@@ -33,7 +40,8 @@ impl SearchMode {
 }
 
 /// Information source for search queries.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Source {
     /// General web search (default).
     #[default]
@@ -82,40 +90,343 @@ impl TryFrom<&str> for Source {
     }
 }
 
+/// Options sent with a file when requesting its upload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadOptions {
+    /// Have the server treat the file as an image.
+    pub force_image: bool,
+    /// Skip server-side text extraction of the file.
+    pub skip_parsing: bool,
+    /// Keep the file beyond the thread it was attached to.
+    pub persistent_upload: bool,
+    /// MIME type of the file, overriding detection from its name and contents.
+    pub content_type: Option<String>,
+    /// Upload source reported to the server; `"default"` when unset.
+    pub source: Option<String>,
+}
+
 /// A file to be uploaded with a search query.
 #[derive(Debug, Clone)]
 pub enum UploadFile {
     /// File contents as bytes with a filename.
-    Binary { filename: String, data: Bytes },
-    /// File contents as text with a filename.
-    Text { filename: String, content: String },
+    Binary { filename: String, data: Bytes, options: UploadOptions },
+    /// File contents as UTF-8 text with a filename.
+    ///
+    /// Held as [`Bytes`] so uploading the text does not copy it.
+    Text { filename: String, content: Bytes, options: UploadOptions },
+    /// File streamed from disk when uploaded.
+    Path { filename: String, path: PathBuf, len: u64, options: UploadOptions },
+    /// File streamed from an async reader when uploaded.
+    Reader { filename: String, reader: UploadReader, len: u64, options: UploadOptions },
 }
 
 impl UploadFile {
     /// Creates an `UploadFile` from bytes.
     pub fn from_bytes(filename: impl Into<String>, data: impl Into<Bytes>) -> Self {
-        Self::Binary { filename: filename.into(), data: data.into() }
+        Self::Binary {
+            filename: filename.into(),
+            data: data.into(),
+            options: UploadOptions::default(),
+        }
     }
 
     /// Creates an `UploadFile` from text content.
     pub fn from_text(filename: impl Into<String>, content: impl Into<String>) -> Self {
-        Self::Text { filename: filename.into(), content: content.into() }
+        Self::Text {
+            filename: filename.into(),
+            content: Bytes::from(content.into()),
+            options: UploadOptions::default(),
+        }
+    }
+
+    /// Creates an `UploadFile` that streams the file at `path`.
+    ///
+    /// The filename is taken from the path and the size from its metadata;
+    /// the contents are read only while uploading.
+    pub async fn from_path(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let len = tokio::fs::metadata(&path).await?.len();
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Self::Path { filename, path, len, options: UploadOptions::default() })
+    }
+
+    /// Creates an `UploadFile` that streams `len` bytes from `reader`.
+    ///
+    /// The reader is consumed by the first upload; clones share it.
+    pub fn from_reader<R>(filename: impl Into<String>, reader: R, len: u64) -> Self
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        Self::Reader {
+            filename: filename.into(),
+            reader: UploadReader::new(reader),
+            len,
+            options: UploadOptions::default(),
+        }
+    }
+
+    /// Replaces the upload options of the file.
+    #[must_use]
+    pub fn with_options(mut self, options: UploadOptions) -> Self {
+        *self.options_mut() = options;
+        self
+    }
+
+    /// Sets the MIME type of the file instead of detecting it.
+    #[must_use]
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.options_mut().content_type = Some(content_type.into());
+        self
+    }
+
+    /// Returns the upload options of the file.
+    pub fn options(&self) -> &UploadOptions {
+        match self {
+            Self::Binary { options, .. }
+            | Self::Text { options, .. }
+            | Self::Path { options, .. }
+            | Self::Reader { options, .. } => options,
+        }
+    }
+
+    fn options_mut(&mut self) -> &mut UploadOptions {
+        match self {
+            Self::Binary { options, .. }
+            | Self::Text { options, .. }
+            | Self::Path { options, .. }
+            | Self::Reader { options, .. } => options,
+        }
     }
 
     pub(crate) fn filename(&self) -> &str {
         match self {
-            Self::Binary { filename, .. } | Self::Text { filename, .. } => filename,
+            Self::Binary { filename, .. }
+            | Self::Text { filename, .. }
+            | Self::Path { filename, .. }
+            | Self::Reader { filename, .. } => filename,
         }
     }
 
-    pub(crate) fn len(&self) -> usize {
+    /// Returns the size of the file contents in bytes.
+    pub fn size(&self) -> u64 {
         match self {
-            Self::Binary { data, .. } => data.len(),
-            Self::Text { content, .. } => content.len(),
+            Self::Binary { data, .. } => data.len() as u64,
+            Self::Text { content, .. } => content.len() as u64,
+            Self::Path { len, .. } | Self::Reader { len, .. } => *len,
         }
     }
 }
 
+pub(crate) type BoxedReader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// Stage of a single file's upload reported through [`UploadProgress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadStage {
+    /// Bytes of the file handed to the S3 upload so far.
+    Sending { sent: u64, total: u64 },
+    /// The file was stored in S3.
+    Uploaded,
+    /// Server-side processing of the file started.
+    Processing,
+    /// Server-side processing of the file finished.
+    Processed,
+    /// The server rejected the file during processing.
+    Rejected,
+}
+
+/// Server-side processing result of an uploaded file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachmentStatus {
+    /// The server processed the file.
+    Processed,
+    /// The server failed to process or rejected the file.
+    Failed { reason: String },
+    /// The processing stream ended without reporting on the file.
+    ///
+    /// Such files are not attached to searches, see
+    /// [`Error::AttachmentUnconfirmed`](crate::Error::AttachmentUnconfirmed).
+    Unconfirmed,
+}
+
+/// Outcome of uploading and processing a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachmentOutcome {
+    /// Filename of the file.
+    pub filename: String,
+    /// Server-assigned UUID of the file.
+    pub file_uuid: String,
+    /// S3 object URL to reference the file as an attachment.
+    pub s3_object_url: String,
+    /// Processing result.
+    pub status: AttachmentStatus,
+    /// Number of pages extracted, if reported.
+    pub page_count: Option<u64>,
+    /// Number of characters extracted, if reported.
+    pub char_count: Option<u64>,
+    /// Whether the extracted content was truncated.
+    pub truncated: bool,
+}
+
+/// Limits checked before any file of a batch is sent.
+///
+/// All limits are unset by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadPolicy {
+    /// Maximum size of a single file in bytes.
+    pub max_file_size: Option<u64>,
+    /// Maximum combined size of all files of a batch in bytes.
+    pub max_batch_size: Option<u64>,
+    /// Maximum number of files in a batch.
+    pub max_files: Option<usize>,
+    /// MIME types that may be uploaded, e.g. `application/pdf` or `image/*`.
+    /// Any type is allowed when empty.
+    pub allowed_content_types: Vec<String>,
+}
+
+impl UploadPolicy {
+    /// Creates a policy without limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum size of a single file in bytes.
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Sets the maximum combined size of a batch in bytes.
+    pub fn max_batch_size(mut self, bytes: u64) -> Self {
+        self.max_batch_size = Some(bytes);
+        self
+    }
+
+    /// Sets the maximum number of files in a batch.
+    pub fn max_files(mut self, count: usize) -> Self {
+        self.max_files = Some(count);
+        self
+    }
+
+    /// Adds a MIME type to the allowlist; `type/*` allows a whole top-level type.
+    pub fn allow_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.allowed_content_types.push(content_type.into());
+        self
+    }
+
+    /// Checks the number and sizes of the files of a batch.
+    pub(crate) fn check_sizes(&self, files: &[&UploadFile]) -> crate::Result<()> {
+        if let Some(limit) = self.max_files
+            && files.len() > limit
+        {
+            return Err(crate::Error::TooManyFiles { count: files.len(), limit });
+        }
+        if let Some(limit) = self.max_file_size
+            && let Some(file) = files.iter().find(|file| file.size() > limit)
+        {
+            return Err(crate::Error::FileTooLarge {
+                filename: file.filename().to_string(),
+                size: file.size(),
+                limit,
+            });
+        }
+        let total: u64 = files.iter().map(|file| file.size()).sum();
+        if let Some(limit) = self.max_batch_size
+            && total > limit
+        {
+            return Err(crate::Error::BatchTooLarge { size: total, limit });
+        }
+        Ok(())
+    }
+
+    /// Checks the content type of a file against the allowlist.
+    pub(crate) fn check_content_type(
+        &self,
+        filename: &str,
+        content_type: &str,
+    ) -> crate::Result<()> {
+        if self.allowed_content_types.is_empty() {
+            return Ok(());
+        }
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        let allowed = self.allowed_content_types.iter().any(|pattern| {
+            match pattern.strip_suffix("/*") {
+                Some(top_level) => essence
+                    .split_once('/')
+                    .is_some_and(|(ty, _)| ty.eq_ignore_ascii_case(top_level)),
+                None => essence.eq_ignore_ascii_case(pattern.trim()),
+            }
+        });
+        if allowed {
+            Ok(())
+        } else {
+            Err(crate::Error::ContentTypeNotAllowed {
+                filename: filename.to_string(),
+                content_type: content_type.to_string(),
+            })
+        }
+    }
+}
+
+/// A progress update for one file of an upload batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadProgress {
+    /// Position of the file in the uploaded batch.
+    pub index: usize,
+    /// Filename of the file.
+    pub filename: String,
+    /// Server-assigned UUID of the file.
+    pub file_uuid: String,
+    /// Current upload stage.
+    pub stage: UploadStage,
+}
+
+/// Callback receiving [`UploadProgress`] updates.
+///
+/// Invoked from the upload tasks, so it should return quickly.
+#[derive(Clone)]
+pub struct UploadProgressHandler(Arc<dyn Fn(UploadProgress) + Send + Sync>);
+
+impl UploadProgressHandler {
+    /// Wraps a callback into a handler.
+    pub fn new(callback: impl Fn(UploadProgress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    pub(crate) fn emit(&self, progress: UploadProgress) {
+        (self.0)(progress);
+    }
+}
+
+impl fmt::Debug for UploadProgressHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UploadProgressHandler").finish_non_exhaustive()
+    }
+}
+
+/// A single-use async reader backing [`UploadFile::Reader`].
+#[derive(Clone)]
+pub struct UploadReader(Arc<Mutex<Option<BoxedReader>>>);
+
+impl UploadReader {
+    fn new(reader: impl AsyncRead + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Some(Box::pin(reader)))))
+    }
+
+    /// Takes the reader, leaving `None` for later uploads.
+    pub(crate) fn take(&self) -> Option<BoxedReader> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).take()
+    }
+}
+
+impl fmt::Debug for UploadReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UploadReader").finish_non_exhaustive()
+    }
+}
+
 /// Parameters for a search request.
 #[derive(Debug, Clone, Default)]
 pub struct SearchRequest {
@@ -125,14 +436,22 @@ pub struct SearchRequest {
     pub mode: SearchMode,
     /// Optional explicit model preference.
     pub model_preference: Option<ModelPreference>,
+    /// Models to try, in order, when the preferred model is unavailable.
+    pub fallback_models: Vec<ModelPreference>,
     /// Information sources: Web, Scholar, Social.
     pub sources: Vec<Source>,
     /// Files to upload with the query.
     pub files: Vec<UploadFile>,
+    /// S3 object URLs of previously uploaded files to attach to the query.
+    pub attachment_urls: Vec<String>,
+    /// Callback receiving progress of the file uploads.
+    pub upload_progress: Option<UploadProgressHandler>,
     /// Language code (ISO 639), e.g., "en-US".
     pub language: String,
     /// Context from a previous query for follow-up.
     pub follow_up: Option<FollowUpContext>,
+    /// UUID of the Space to search within.
+    pub space: Option<String>,
     /// Whether to enable incognito mode.
     pub incognito: bool,
 }
@@ -144,10 +463,14 @@ impl SearchRequest {
             query: query.into(),
             mode: SearchMode::Auto,
             model_preference: None,
+            fallback_models: Vec::new(),
             sources: vec![Source::Web],
             files: Vec::new(),
+            attachment_urls: Vec::new(),
+            upload_progress: None,
             language: "en-US".to_string(),
             follow_up: None,
+            space: None,
             incognito: false,
         }
     }
@@ -164,6 +487,27 @@ impl SearchRequest {
         self
     }
 
+    /// Sets an ordered chain of models.
+    ///
+    /// The first model is preferred, the rest are tried in order when the
+    /// previous one is temporarily unavailable (HTTP 503).
+    pub fn models<I, M>(mut self, models: I) -> Self
+    where
+        I: IntoIterator<Item = M>,
+        M: Into<ModelPreference>,
+    {
+        let mut models = models.into_iter().map(Into::into);
+        self.model_preference = models.next();
+        self.fallback_models = models.collect();
+        self
+    }
+
+    /// Adds a model to try when the preferred model is unavailable.
+    pub fn fallback_model(mut self, model: impl Into<ModelPreference>) -> Self {
+        self.fallback_models.push(model.into());
+        self
+    }
+
     /// Sets the information sources.
     pub fn sources(mut self, sources: Vec<Source>) -> Self {
         self.sources = sources;
@@ -176,6 +520,32 @@ impl SearchRequest {
         self
     }
 
+    /// Attaches a previously uploaded file by its S3 object URL, as returned
+    /// by [`Client::upload_files`](crate::Client::upload_files).
+    pub fn attachment_url(mut self, url: impl Into<String>) -> Self {
+        self.attachment_urls.push(url.into());
+        self
+    }
+
+    /// Attaches several previously uploaded files by their S3 object URLs.
+    pub fn attachment_urls<I, U>(mut self, urls: I) -> Self
+    where
+        I: IntoIterator<Item = U>,
+        U: Into<String>,
+    {
+        self.attachment_urls.extend(urls.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets a callback receiving progress of the file uploads.
+    pub fn on_upload_progress(
+        mut self,
+        callback: impl Fn(UploadProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.upload_progress = Some(UploadProgressHandler::new(callback));
+        self
+    }
+
     /// Sets the language.
     pub fn language(mut self, language: impl Into<String>) -> Self {
         self.language = language.into();
@@ -188,11 +558,25 @@ impl SearchRequest {
         self
     }
 
+    /// Scopes the query to a Space, applying its instructions and files.
+    ///
+    /// Takes the Space UUID, see [`Space::uuid`].
+    pub fn space(mut self, uuid: impl Into<String>) -> Self {
+        self.space = Some(uuid.into());
+        self
+    }
+
     /// Enables or disables incognito mode.
     pub fn incognito(mut self, incognito: bool) -> Self {
         self.incognito = incognito;
         self
     }
+
+    /// Returns the explicit model preference or the mode's default one.
+    pub(crate) fn primary_model(&self) -> ModelPreference {
+        self.model_preference
+            .unwrap_or_else(|| ModelPreference::new(self.mode.default_preference()))
+    }
 }
 
 /// Context for follow-up queries, extracted from a previous response.
@@ -202,10 +586,26 @@ pub struct FollowUpContext {
     pub backend_uuid: Option<String>,
     /// Attachment URLs from the previous response.
     pub attachments: Vec<String>,
+    /// Query that produced the previous response, used by
+    /// [`Client::rewrite`](crate::Client::rewrite).
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Sources of the previous query, reused by
+    /// [`Client::rewrite`](crate::Client::rewrite).
+    #[serde(default)]
+    pub sources: Vec<Source>,
+    /// Language of the previous query, reused by
+    /// [`Client::rewrite`](crate::Client::rewrite).
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Space of the previous query, reused by
+    /// [`Client::rewrite`](crate::Client::rewrite).
+    #[serde(default)]
+    pub space: Option<String>,
 }
 
 /// A single event from the SSE stream.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct SearchEvent {
     /// The extracted answer text, if available.
     #[serde(default)]
@@ -219,7 +619,28 @@ pub struct SearchEvent {
     /// Attachment URLs associated with this response.
     #[serde(default)]
     pub attachments: Vec<String>,
+    /// Model that produced the answer, as displayed by Perplexity.
+    #[serde(default)]
+    pub display_model: Option<String>,
+    /// Response mode reported by the server, e.g. `"COPILOT"`.
+    #[serde(default)]
+    pub mode: Option<String>,
+    /// Thread slug used to build the shareable thread URL.
+    #[serde(default)]
+    pub thread_url_slug: Option<String>,
+    /// Processing status reported by the server, e.g. `"COMPLETED"`.
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Whether the server marked this event as the final message.
+    #[serde(default)]
+    pub is_final: bool,
+    /// Frontend UUID echoed back by the server.
+    #[serde(default)]
+    pub frontend_uuid: Option<String>,
     /// The full raw JSON value from the SSE event.
+    ///
+    /// `Null` when the client is built with
+    /// [`keep_raw(false)`](crate::ClientBuilder::keep_raw).
     pub raw: serde_json::Value,
 }
 
@@ -229,6 +650,7 @@ impl SearchEvent {
         FollowUpContext {
             backend_uuid: self.backend_uuid.clone(),
             attachments: self.attachments.clone(),
+            ..FollowUpContext::default()
         }
     }
 }
@@ -250,10 +672,380 @@ pub struct SearchResponse {
     pub web_results: Vec<SearchWebResult>,
     /// Context for making follow-up queries.
     pub follow_up: FollowUpContext,
+    /// The model preference that answered the query.
+    pub model_preference: ModelPreference,
+    /// Typed metadata parsed from the stream.
+    pub metadata: ResponseMetadata,
     /// The last raw event from the stream.
+    ///
+    /// `Null` when the client is built with
+    /// [`keep_raw(false)`](crate::ClientBuilder::keep_raw).
     pub raw: serde_json::Value,
 }
 
+/// Metadata about a completed search, parsed from the last stream event.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseMetadata {
+    /// Model that produced the answer, as displayed by Perplexity.
+    pub display_model: Option<String>,
+    /// Response mode reported by the server, e.g. `"COPILOT"`.
+    pub mode: Option<String>,
+    /// Thread slug used to build the shareable thread URL.
+    pub thread_url_slug: Option<String>,
+    /// Processing status reported by the server, e.g. `"COMPLETED"`.
+    pub status: Option<String>,
+    /// Whether the server marked the last event as the final message.
+    pub is_final: bool,
+    /// Frontend UUID echoed back by the server.
+    pub frontend_uuid: Option<String>,
+    /// Backend UUID of the answer.
+    pub backend_uuid: Option<String>,
+    /// Client-side timings of the request.
+    pub timings: ResponseTimings,
+}
+
+impl ResponseMetadata {
+    /// Returns the shareable thread URL, if the thread slug is known.
+    pub fn thread_url(&self) -> Option<String> {
+        self.thread_url_slug.as_ref().map(|slug| format!("{API_BASE_URL}{THREAD_PATH}/{slug}"))
+    }
+}
+
+/// Client-side timings, measured from the start of the search call.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResponseTimings {
+    /// Time until the first SSE event arrived, including file uploads.
+    pub time_to_first_event: Option<Duration>,
+    /// Total time until the stream completed.
+    pub total: Duration,
+}
+
+/// Remaining query allowances of the account, from
+/// [`Client::quota`](crate::Client::quota).
+///
+/// A `None` allowance was not reported by the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    /// Remaining Pro searches.
+    #[serde(default)]
+    pub pro_remaining: Option<u32>,
+    /// Remaining reasoning searches, if counted apart from Pro searches.
+    #[serde(default)]
+    pub reasoning_remaining: Option<u32>,
+    /// Remaining deep research searches.
+    #[serde(default)]
+    pub research_remaining: Option<u32>,
+}
+
+impl Quota {
+    /// Returns the remaining searches in `mode`; `None` when unknown or unlimited.
+    pub fn remaining(&self, mode: SearchMode) -> Option<u32> {
+        match mode {
+            SearchMode::Auto => None,
+            SearchMode::Pro => self.pro_remaining,
+            SearchMode::Reasoning => self.reasoning_remaining.or(self.pro_remaining),
+            SearchMode::DeepResearch => self.research_remaining,
+        }
+    }
+
+    /// Returns `true` if no searches in `mode` remain.
+    pub fn is_exhausted(&self, mode: SearchMode) -> bool {
+        self.remaining(mode) == Some(0)
+    }
+}
+
+/// Searches sent per mode by a client and its clones, counted locally.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Searches in [`SearchMode::Auto`].
+    pub auto: u64,
+    /// Searches in [`SearchMode::Pro`].
+    pub pro: u64,
+    /// Searches in [`SearchMode::Reasoning`].
+    pub reasoning: u64,
+    /// Searches in [`SearchMode::DeepResearch`].
+    pub deep_research: u64,
+}
+
+impl Usage {
+    /// Returns the number of searches sent in `mode`.
+    pub fn get(&self, mode: SearchMode) -> u64 {
+        match mode {
+            SearchMode::Auto => self.auto,
+            SearchMode::Pro => self.pro,
+            SearchMode::Reasoning => self.reasoning,
+            SearchMode::DeepResearch => self.deep_research,
+        }
+    }
+}
+
+/// When and how often failed searches are retried.
+///
+/// Searches are not retried by default. Only errors that may pass on their
+/// own are retried, see [`Error::is_retryable`](crate::Error::is_retryable).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every further retry.
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy that does not retry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of retries after the first attempt.
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Sets the delay before the first retry.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the upper bound of the delay between retries.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Returns the delay before the given retry, counted from zero.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff.saturating_mul(1 << retry.min(16)).min(self.max_backoff)
+    }
+}
+
+/// Limits of a [`Client::search_batch`](crate::Client::search_batch) run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchLimits {
+    /// Maximum number of searches in flight at once.
+    pub concurrency: usize,
+    /// Maximum number of requests started per minute, retries included.
+    pub requests_per_minute: Option<u32>,
+}
+
+impl BatchLimits {
+    /// Creates limits allowing `concurrency` searches in flight at once.
+    pub fn new(concurrency: usize) -> Self {
+        Self { concurrency, requests_per_minute: None }
+    }
+
+    /// Sets the maximum number of requests started per minute.
+    pub fn requests_per_minute(mut self, requests: u32) -> Self {
+        self.requests_per_minute = Some(requests);
+        self
+    }
+}
+
+impl From<usize> for BatchLimits {
+    fn from(concurrency: usize) -> Self {
+        Self::new(concurrency)
+    }
+}
+
+/// Answers of several models to the same query, from
+/// [`Client::compare`](crate::Client::compare).
+#[derive(Debug)]
+pub struct Comparison {
+    /// One entry per compared model, in the order the models were given.
+    pub entries: Vec<ComparisonEntry>,
+    /// Source URLs cited by every model that answered. Empty unless at least
+    /// two models answered.
+    pub shared_urls: Vec<String>,
+}
+
+impl Comparison {
+    /// Returns the entries whose model answered successfully.
+    pub fn successful(&self) -> impl Iterator<Item = (&ComparisonEntry, &SearchResponse)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.result.as_ref().ok().map(|response| (entry, response)))
+    }
+}
+
+/// The answer of one model in a [`Comparison`].
+#[derive(Debug)]
+pub struct ComparisonEntry {
+    /// The model the query was sent to.
+    pub model: ModelPreference,
+    /// The model's response, or why it failed.
+    pub result: crate::Result<SearchResponse>,
+    /// Source URLs cited by this model and no other.
+    pub unique_urls: Vec<String>,
+    /// Time the model took to answer or fail.
+    pub elapsed: Duration,
+}
+
+/// A page of the thread library to list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    /// Maximum number of threads to return.
+    pub limit: u32,
+    /// Number of threads to skip, newest first.
+    pub offset: u32,
+}
+
+impl Pagination {
+    /// Creates a page of `limit` threads starting after `offset` threads.
+    pub fn new(limit: u32, offset: u32) -> Self {
+        Self { limit, offset }
+    }
+
+    /// Returns the page following this one.
+    #[must_use]
+    pub fn next(self) -> Self {
+        Self { offset: self.offset.saturating_add(self.limit), ..self }
+    }
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self { limit: 20, offset: 0 }
+    }
+}
+
+/// A thread in the user's library, as listed by
+/// [`Client::list_threads`](crate::Client::list_threads).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadSummary {
+    /// Thread slug used in the thread URL.
+    #[serde(default)]
+    pub slug: String,
+    /// Thread title, usually the first query.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Backend UUID of the thread's latest entry.
+    #[serde(default)]
+    pub uuid: Option<String>,
+    /// Context UUID shared by all entries of the thread.
+    #[serde(default)]
+    pub context_uuid: Option<String>,
+    /// Number of queries in the thread.
+    #[serde(default)]
+    pub query_count: Option<u64>,
+    /// Time of the latest query, as reported by the server.
+    #[serde(default)]
+    pub last_query_datetime: Option<String>,
+    /// Response mode of the thread, e.g. `"COPILOT"`.
+    #[serde(default)]
+    pub mode: Option<String>,
+    /// Model of the latest answer, as displayed by Perplexity.
+    #[serde(default)]
+    pub display_model: Option<String>,
+}
+
+impl ThreadSummary {
+    /// Returns the shareable thread URL.
+    pub fn url(&self) -> String {
+        format!("{API_BASE_URL}{THREAD_PATH}/{}", self.slug)
+    }
+}
+
+/// A thread with every query and answer, as returned by
+/// [`Client::get_thread`](crate::Client::get_thread).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Thread {
+    /// Thread slug used in the thread URL.
+    pub slug: String,
+    /// Queries and answers of the thread, oldest first.
+    pub entries: Vec<ThreadEntry>,
+}
+
+impl Thread {
+    /// Returns the shareable thread URL.
+    pub fn url(&self) -> String {
+        format!("{API_BASE_URL}{THREAD_PATH}/{}", self.slug)
+    }
+}
+
+/// A single query and its answer within a [`Thread`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadEntry {
+    /// The query string.
+    pub query: String,
+    /// The answer text, if any.
+    pub answer: Option<String>,
+    /// Web search results cited by the answer.
+    pub web_results: Vec<SearchWebResult>,
+    /// Backend UUID of the entry.
+    pub backend_uuid: Option<String>,
+    /// Attachment URLs of the entry.
+    pub attachments: Vec<String>,
+    /// Model that produced the answer, as displayed by Perplexity.
+    pub display_model: Option<String>,
+    /// Response mode reported by the server, e.g. `"COPILOT"`.
+    pub mode: Option<String>,
+    /// Time the entry was last updated, as reported by the server.
+    pub updated_datetime: Option<String>,
+    /// Token authorizing changes to the thread.
+    #[serde(skip)]
+    pub(crate) read_write_token: Option<String>,
+}
+
+/// A Space (collection) of the user, with its custom instructions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Space {
+    /// UUID of the Space, used with [`SearchRequest::space`].
+    #[serde(default)]
+    pub uuid: String,
+    /// Slug used in the Space URL.
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// Title of the Space.
+    #[serde(default)]
+    pub title: String,
+    /// Description of the Space.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Custom instructions applied to every query in the Space.
+    #[serde(default)]
+    pub instructions: Option<String>,
+    /// Number of threads in the Space.
+    #[serde(default)]
+    pub thread_count: Option<u64>,
+    /// Number of files attached to the Space.
+    #[serde(default)]
+    pub file_count: Option<u64>,
+}
+
+/// A file attached to a [`Space`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceFile {
+    /// Server-assigned UUID of the file.
+    #[serde(default, alias = "file_uuid", alias = "file_id")]
+    pub uuid: String,
+    /// Filename of the file.
+    #[serde(default, alias = "file_name", alias = "name")]
+    pub filename: String,
+    /// Size of the file in bytes, if reported.
+    #[serde(default, alias = "size")]
+    pub file_size: Option<u64>,
+    /// MIME type of the file, if reported.
+    #[serde(default, alias = "file_type")]
+    pub content_type: Option<String>,
+    /// URL of the file contents, if reported.
+    #[serde(default, alias = "file_s3_url", alias = "s3_object_url")]
+    pub url: Option<String>,
+}
+
 #[derive(Serialize)]
 pub(crate) struct AskPayload<'a> {
     pub query_str: &'a str,
@@ -262,17 +1054,19 @@ pub(crate) struct AskPayload<'a> {
 
 #[derive(Serialize)]
 pub(crate) struct AskParams<'a> {
-    pub attachments: Vec<String>,
+    pub attachments: &'a [String],
     pub frontend_context_uuid: String,
     pub frontend_uuid: String,
     pub is_incognito: bool,
     pub language: &'a str,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_backend_uuid: Option<String>,
+    pub last_backend_uuid: Option<&'a str>,
     pub mode: &'static str,
     pub model_preference: &'static str,
     pub source: &'static str,
     pub sources: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_collection_uuid: Option<&'a str>,
     pub version: &'static str,
 }
 
@@ -281,7 +1075,7 @@ pub(crate) struct BatchUploadFileInfo {
     pub filename: String,
     pub content_type: String,
     pub source: String,
-    pub file_size: usize,
+    pub file_size: u64,
     pub force_image: bool,
     pub skip_parsing: bool,
     pub persistent_upload: bool,
@@ -307,3 +1101,72 @@ pub(crate) struct BatchUploadFileMeta {
     pub s3_object_url: String,
     pub uuid: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn upload_policy_checks_count_and_sizes() {
+        let small = UploadFile::from_text("a.txt", "abc");
+        let large = UploadFile::from_bytes("b.bin", vec![0u8; 10]);
+
+        assert!(UploadPolicy::new().check_sizes(&[&small, &large]).is_ok());
+        assert!(matches!(
+            UploadPolicy::new().max_files(1).check_sizes(&[&small, &large]),
+            Err(Error::TooManyFiles { count: 2, limit: 1 })
+        ));
+        assert!(matches!(
+            UploadPolicy::new().max_file_size(5).check_sizes(&[&small, &large]),
+            Err(Error::FileTooLarge { filename, size: 10, limit: 5 }) if filename == "b.bin"
+        ));
+        assert!(matches!(
+            UploadPolicy::new()
+                .max_file_size(10)
+                .max_batch_size(12)
+                .check_sizes(&[&small, &large]),
+            Err(Error::BatchTooLarge { size: 13, limit: 12 })
+        ));
+        assert!(
+            UploadPolicy::new()
+                .max_files(2)
+                .max_batch_size(13)
+                .check_sizes(&[&small, &large])
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn upload_policy_matches_content_types() {
+        let any = UploadPolicy::new();
+        assert!(any.check_content_type("a.exe", "application/x-msdownload").is_ok());
+
+        let policy = UploadPolicy::new()
+            .allow_content_type("application/pdf")
+            .allow_content_type("image/*");
+        assert!(policy.check_content_type("a.pdf", "application/pdf").is_ok());
+        assert!(policy.check_content_type("a.pdf", "Application/PDF; charset=binary").is_ok());
+        assert!(policy.check_content_type("a.png", "image/png").is_ok());
+        assert!(policy.check_content_type("a.svg", "IMAGE/svg+xml").is_ok());
+        assert!(matches!(
+            policy.check_content_type("a.txt", "text/plain"),
+            Err(Error::ContentTypeNotAllowed { filename, .. }) if filename == "a.txt"
+        ));
+        assert!(policy.check_content_type("a.zip", "application/zip").is_err());
+        assert!(policy.check_content_type("a", "imagery/png").is_err());
+    }
+
+    #[test]
+    fn doubles_backoff_up_to_limit() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(500))
+            .max_backoff(Duration::from_secs(3));
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(3));
+        assert_eq!(policy.backoff(40), Duration::from_secs(3));
+    }
+}
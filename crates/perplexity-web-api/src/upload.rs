@@ -1,19 +1,36 @@
+use crate::attachment_cache::AttachmentCache;
 use crate::config::{
     API_BASE_URL, API_REFERER, API_VERSION, ENDPOINT_ATTACHMENT_PROCESSING,
     ENDPOINT_BATCH_UPLOAD_URL,
 };
 use crate::error::{Error, Result};
+use crate::middleware::{MiddlewareStack, RequestKind};
+use crate::parse::{ProcessingEvent, parse_processing_event};
+use crate::sniff::{SNIFF_LEN, detect_content_type};
+use crate::sse::SseFrames;
 use crate::types::{
-    BatchUploadFileInfo, BatchUploadFileMeta, BatchUploadFileResponse, BatchUploadFileResults,
-    UploadFile,
+    AttachmentOutcome, AttachmentStatus, BatchUploadFileInfo, BatchUploadFileMeta,
+    BatchUploadFileResponse, BatchUploadFileResults, UploadFile, UploadPolicy, UploadProgress,
+    UploadProgressHandler, UploadStage,
 };
-use rquest::Client as HttpClient;
+use bytes::Bytes;
+use futures_util::stream::BoxStream;
+use futures_util::{Stream, StreamExt, TryStreamExt};
 use rquest::header::{HeaderValue, ORIGIN, REFERER};
+use rquest::{Body, Client as HttpClient};
 use serde::Serialize;
 use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::time::Instant;
+use tokio_util::io::ReaderStream;
 use uuid::Uuid;
 
+/// Size of the chunks streamed to S3.
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
 const PERPLEXITY_ORIGIN: HeaderValue = HeaderValue::from_static(API_BASE_URL);
 const PERPLEXITY_REFERER: HeaderValue = HeaderValue::from_static(API_REFERER);
 
@@ -27,47 +44,168 @@ struct ProcessingSubscribeRequest {
     file_uuids: Vec<String>,
 }
 
-/// Uploads multiple files in one batch using the presigned-POST flow:
-/// 1. Obtain presigned S3 form fields for all files in a single request
-/// 2. Upload every file to S3 in parallel
-/// 3. Wait for server-side attachment processing of all files via SSE
+/// Reports progress of a single file to the batch's progress handler.
+#[derive(Clone)]
+struct FileProgress {
+    handler: UploadProgressHandler,
+    index: usize,
+    filename: String,
+    file_uuid: String,
+}
+
+impl FileProgress {
+    fn report(&self, stage: UploadStage) {
+        self.handler.emit(UploadProgress {
+            index: self.index,
+            filename: self.filename.clone(),
+            file_uuid: self.file_uuid.clone(),
+            stage,
+        });
+    }
+}
+
+fn report_all(reporters: &[Option<FileProgress>], stage: UploadStage) {
+    for reporter in reporters.iter().flatten() {
+        reporter.report(stage);
+    }
+}
+
+/// A file of a batch that was not found in the attachment cache.
+struct PendingUpload<'a> {
+    /// Position of the file in the batch.
+    index: usize,
+    file: &'a UploadFile,
+    content_type: String,
+    cache_key: Option<String>,
+}
+
+/// Uploads multiple files in one batch.
 ///
-/// Returns one `s3_object_url` per file (same order as input).
+/// Returns one outcome per file (same order as input); files rejected by
+/// the server are reported as failed rather than as an error.
+/// Files are checked against `policy` before anything is sent, and files
+/// found in `cache` are not uploaded again.
+/// Progress of every file is reported to `progress`, if set.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", name = "upload", skip_all, fields(files = files.len()))
+)]
 pub(crate) async fn upload_files(
     http: &HttpClient,
     files: &[&UploadFile],
     timeout: Duration,
-) -> Result<Vec<String>> {
+    policy: &UploadPolicy,
+    cache: Option<&AttachmentCache>,
+    progress: Option<&UploadProgressHandler>,
+    middleware: &MiddlewareStack,
+) -> Result<Vec<AttachmentOutcome>> {
     if files.is_empty() {
         return Ok(Vec::new());
     }
+    policy.check_sizes(files)?;
+
+    let mut outcomes: Vec<Option<AttachmentOutcome>> = vec![None; files.len()];
+    let mut pending = Vec::with_capacity(files.len());
+    for (index, file) in files.iter().enumerate() {
+        let content_type = content_type(file).await?;
+        policy.check_content_type(file.filename(), &content_type)?;
+        let cache_key = match cache {
+            Some(cache) => cache.key(file, &content_type).await?,
+            None => None,
+        };
+        if let (Some(cache), Some(key)) = (cache, &cache_key)
+            && let Some(hit) = cache.get(key, file.filename()).await
+        {
+            if let Some(handler) = progress {
+                let reporter = FileProgress {
+                    handler: handler.clone(),
+                    index,
+                    filename: hit.filename.clone(),
+                    file_uuid: hit.file_uuid.clone(),
+                };
+                reporter.report(UploadStage::Processed);
+            }
+            outcomes[index] = Some(hit);
+            continue;
+        }
+        pending.push(PendingUpload { index, file, content_type, cache_key });
+    }
 
+    if !pending.is_empty() {
+        let uploaded = upload_batch(http, &pending, timeout, progress, middleware).await?;
+        for (pending, outcome) in pending.into_iter().zip(uploaded) {
+            if let (Some(cache), Some(key)) = (cache, pending.cache_key) {
+                cache.insert(key, &outcome).await;
+            }
+            outcomes[pending.index] = Some(outcome);
+        }
+    }
+
+    Ok(outcomes.into_iter().flatten().collect())
+}
+
+/// Uploads files using the presigned-POST flow:
+/// 1. Obtain presigned S3 form fields for all files in a single request
+/// 2. Upload every file to S3 in parallel
+/// 3. Wait for server-side attachment processing of all files via SSE
+///
+/// Returns one outcome per file (same order as `pending`).
+async fn upload_batch(
+    http: &HttpClient,
+    pending: &[PendingUpload<'_>],
+    timeout: Duration,
+    progress: Option<&UploadProgressHandler>,
+    middleware: &MiddlewareStack,
+) -> Result<Vec<AttachmentOutcome>> {
     // Build client-UUID -> file mapping so we can correlate response entries
     // back to the original files while preserving input order.
-    let keyed: Vec<(String, &UploadFile)> =
-        files.iter().map(|f| (Uuid::new_v4().to_string(), *f)).collect();
+    let keyed: Vec<(String, &PendingUpload<'_>)> =
+        pending.iter().map(|upload| (Uuid::new_v4().to_string(), upload)).collect();
 
     // Step 1: obtain presigned upload fields for all files at once
-    let batch_resp = request_upload_urls(http, &keyed, timeout).await?;
+    let batch_resp = request_upload_urls(http, &keyed, timeout, middleware).await?;
 
     // Collect per-file metadata preserving original order
-    let file_metas: Vec<(BatchUploadFileMeta, &BatchUploadFileResults, &UploadFile)> = keyed
+    let file_metas: Vec<(BatchUploadFileMeta, &BatchUploadFileResults, &PendingUpload<'_>)> =
+        keyed
+            .iter()
+            .map(|(client_uuid, upload)| {
+                let results =
+                    batch_resp.results.get(client_uuid).ok_or(Error::MissingUploadResponse)?;
+                let meta = BatchUploadFileMeta {
+                    s3_object_url: results.s3_object_url.clone(),
+                    uuid: results.file_uuid.clone(),
+                };
+                Ok((meta, results, *upload))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+    let reporters: Vec<Option<FileProgress>> = file_metas
         .iter()
-        .map(|(client_uuid, file)| {
-            let results =
-                batch_resp.results.get(client_uuid).ok_or(Error::MissingUploadResponse)?;
-            let meta = BatchUploadFileMeta {
-                s3_object_url: results.s3_object_url.clone(),
-                uuid: results.file_uuid.clone(),
-            };
-            Ok((meta, results, *file))
+        .map(|(meta, _, upload)| {
+            progress.map(|handler| FileProgress {
+                handler: handler.clone(),
+                index: upload.index,
+                filename: upload.file.filename().to_string(),
+                file_uuid: meta.uuid.clone(),
+            })
         })
-        .collect::<Result<Vec<_>>>()?;
+        .collect();
 
     // Step 2: upload every file to S3 in parallel
     let s3_futures: Vec<_> = file_metas
         .iter()
-        .map(|(_, results, file)| upload_to_s3(http, results, file, timeout))
+        .zip(&reporters)
+        .map(|((_, results, upload), reporter)| {
+            upload_to_s3(
+                http,
+                results,
+                upload.file,
+                &upload.content_type,
+                timeout,
+                reporter.clone(),
+            )
+        })
         .collect();
 
     let s3_results = futures_util::future::join_all(s3_futures).await;
@@ -76,33 +214,113 @@ pub(crate) async fn upload_files(
     }
 
     // Step 3: wait for server-side attachment processing
-    let file_uuids: Vec<String> = file_metas.iter().map(|(m, _, _)| m.uuid.clone()).collect();
-    wait_for_processing(http, &file_uuids, timeout).await?;
+    let file_uuids: Vec<String> = file_metas.iter().map(|(m, ..)| m.uuid.clone()).collect();
+    report_all(&reporters, UploadStage::Processing);
+    let processed = wait_for_processing(http, &file_uuids, timeout, &reporters).await?;
 
-    let urls = file_metas.into_iter().map(|(m, _, _)| m.s3_object_url).collect();
-    Ok(urls)
+    let outcomes = file_metas
+        .into_iter()
+        .zip(processed)
+        .map(|((meta, _, upload), event)| AttachmentOutcome {
+            filename: upload.file.filename().to_string(),
+            file_uuid: meta.uuid,
+            s3_object_url: meta.s3_object_url,
+            status: processing_status(event.as_ref()),
+            page_count: event.as_ref().and_then(|event| event.page_count),
+            char_count: event.as_ref().and_then(|event| event.char_count),
+            truncated: event.as_ref().and_then(|event| event.truncated).unwrap_or(false),
+        })
+        .collect();
+    Ok(outcomes)
+}
+
+/// Returns the status of a file from its final processing event, if the
+/// processing stream reported on it.
+fn processing_status(event: Option<&ProcessingEvent>) -> AttachmentStatus {
+    match event.map(ProcessingEvent::failure_reason) {
+        Some(Some(reason)) => AttachmentStatus::Failed { reason },
+        Some(None) => AttachmentStatus::Processed,
+        None => AttachmentStatus::Unconfirmed,
+    }
+}
+
+/// Returns the `s3_object_url` of every outcome, or an error for the first
+/// file that was not processed.
+pub(crate) fn accepted_urls(outcomes: Vec<AttachmentOutcome>) -> Result<Vec<String>> {
+    outcomes
+        .into_iter()
+        .map(|outcome| match outcome.status {
+            AttachmentStatus::Processed => Ok(outcome.s3_object_url),
+            AttachmentStatus::Failed { reason } => {
+                Err(Error::AttachmentRejected { filename: outcome.filename, reason })
+            }
+            AttachmentStatus::Unconfirmed => {
+                Err(Error::AttachmentUnconfirmed { filename: outcome.filename })
+            }
+        })
+        .collect()
+}
+
+/// Returns the explicit content type of a file, or detects it from the
+/// filename and the leading bytes of its contents.
+///
+/// An explicit content type is checked here, before the upload is
+/// registered, rather than when the file is sent to S3.
+///
+/// Readers cannot be peeked without consuming them, so only their filename
+/// is used.
+async fn content_type(file: &UploadFile) -> Result<String> {
+    if let Some(content_type) = &file.options().content_type {
+        content_type
+            .parse::<mime_guess::Mime>()
+            .map_err(|e| Error::InvalidMimeType(format!("{content_type}: {e}")))?;
+        return Ok(content_type.clone());
+    }
+
+    let head = match file {
+        UploadFile::Binary { data, .. } | UploadFile::Text { content: data, .. } => {
+            Some(data[..data.len().min(SNIFF_LEN)].to_vec())
+        }
+        UploadFile::Path { path, .. } => {
+            let source = tokio::fs::File::open(path).await.map_err(Error::UploadSource)?;
+            let mut head = Vec::with_capacity(SNIFF_LEN);
+            source
+                .take(SNIFF_LEN as u64)
+                .read_to_end(&mut head)
+                .await
+                .map_err(Error::UploadSource)?;
+            Some(head)
+        }
+        UploadFile::Reader { .. } => None,
+    };
+    Ok(detect_content_type(file.filename(), head.as_deref()))
 }
 
 /// Step 1: single batch request to obtain presigned S3 credentials for all files.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(files = keyed.len()))
+)]
 async fn request_upload_urls(
     http: &HttpClient,
-    keyed: &[(String, &UploadFile)],
+    keyed: &[(String, &PendingUpload<'_>)],
     timeout: Duration,
+    middleware: &MiddlewareStack,
 ) -> Result<BatchUploadFileResponse> {
     let mut files = HashMap::with_capacity(keyed.len());
-    for (client_uuid, file) in keyed {
-        let content_type =
-            mime_guess::from_path(file.filename()).first_or_octet_stream().to_string();
+    for (client_uuid, upload) in keyed {
+        let PendingUpload { file, content_type, .. } = upload;
+        let options = file.options();
         files.insert(
             client_uuid.clone(),
             BatchUploadFileInfo {
                 filename: file.filename().to_string(),
-                content_type,
-                source: "default".to_string(),
-                file_size: file.len(),
-                force_image: false,
-                skip_parsing: false,
-                persistent_upload: false,
+                content_type: content_type.clone(),
+                source: options.source.clone().unwrap_or_else(|| "default".to_string()),
+                file_size: file.size(),
+                force_image: options.force_image,
+                skip_parsing: options.skip_parsing,
+                persistent_upload: options.persistent_upload,
             },
         );
     }
@@ -111,13 +329,14 @@ async fn request_upload_urls(
         "{API_BASE_URL}{ENDPOINT_BATCH_UPLOAD_URL}?version={API_VERSION}&source=default"
     );
 
-    let fut = http
-        .post(&full_url)
+    let body = serde_json::to_value(BatchUploadUrlRequest { files })?;
+    let fut = middleware
+        .on_request(RequestKind::Upload, full_url, body)?
+        .build(http)
         .header(ORIGIN, PERPLEXITY_ORIGIN)
         .header(REFERER, PERPLEXITY_REFERER)
         .header("x-app-apiclient", "default")
         .header("x-app-apiversion", API_VERSION)
-        .json(&BatchUploadUrlRequest { files })
         .send();
 
     let resp = tokio::time::timeout(timeout, fut)
@@ -131,49 +350,154 @@ async fn request_upload_urls(
 }
 
 /// Step 2: upload a single file to S3 using the presigned form fields.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(filename = file.filename(), size = file.size()),
+    )
+)]
 async fn upload_to_s3(
     http: &HttpClient,
     results: &BatchUploadFileResults,
     file: &UploadFile,
+    content_type: &str,
     timeout: Duration,
+    reporter: Option<FileProgress>,
 ) -> Result<()> {
-    let content_type =
-        mime_guess::from_path(file.filename()).first_or_octet_stream().to_string();
-
     let mut form = rquest::multipart::Form::new();
     for (key, value) in &results.fields {
         form = form.text(key.clone(), value.clone());
     }
 
-    let raw: Vec<u8> = match file {
-        UploadFile::Binary { data, .. } => data.to_vec(),
-        UploadFile::Text { content, .. } => content.as_bytes().to_vec(),
-    };
-    let file_part = rquest::multipart::Part::bytes(raw)
+    let activity = Activity::new();
+    let chunks = upload_chunks(file, reporter.clone()).await?;
+    let body = Body::wrap_stream(chunks.inspect_ok({
+        let activity = activity.clone();
+        move |_| activity.touch()
+    }));
+    let file_part = rquest::multipart::Part::stream_with_length(body, file.size())
         .file_name(file.filename().to_string())
-        .mime_str(&content_type)
+        .mime_str(content_type)
         .map_err(|e| Error::InvalidMimeType(e.to_string()))?;
     form = form.part("file", file_part);
 
     let fut = http.post(&results.s3_bucket_url).multipart(form).send();
 
-    tokio::time::timeout(timeout, fut)
-        .await
-        .map_err(|_| Error::Timeout(timeout))?
+    until_stalled(fut, &activity, timeout)
+        .await?
         .map_err(Error::UploadRequest)?
         .error_for_status()
         .map_err(Error::S3UploadFailed)?;
 
+    if let Some(reporter) = reporter {
+        reporter.report(UploadStage::Uploaded);
+    }
+
     Ok(())
 }
 
+/// Time the last chunk of a file was handed to its upload request.
+#[derive(Clone)]
+struct Activity(Arc<Mutex<Instant>>);
+
+impl Activity {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    fn touch(&self) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Instant::now();
+    }
+
+    fn last(&self) -> Instant {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Waits for an upload request, failing with [`Error::Timeout`] once no
+/// chunk was handed to it for `timeout`.
+///
+/// Large files on slow links take far longer than `timeout` to send, so only
+/// a stalled transfer, or a response that does not come after the last
+/// chunk, counts as timed out.
+async fn until_stalled<T>(
+    request: impl Future<Output = T>,
+    activity: &Activity,
+    timeout: Duration,
+) -> Result<T> {
+    let mut request = std::pin::pin!(request);
+    loop {
+        match tokio::time::timeout_at(activity.last() + timeout, &mut request).await {
+            Ok(output) => return Ok(output),
+            Err(_) if activity.last().elapsed() >= timeout => {
+                return Err(Error::Timeout(timeout));
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Streams the contents of a file in chunks, reporting every chunk handed
+/// on as sent.
+async fn upload_chunks(
+    file: &UploadFile,
+    reporter: Option<FileProgress>,
+) -> Result<BoxStream<'static, io::Result<Bytes>>> {
+    let chunks = match file {
+        UploadFile::Binary { data, .. } | UploadFile::Text { content: data, .. } => {
+            chunked(data.clone())
+        }
+        UploadFile::Path { path, .. } => {
+            let source = tokio::fs::File::open(path).await.map_err(Error::UploadSource)?;
+            ReaderStream::with_capacity(source, UPLOAD_CHUNK_SIZE).boxed()
+        }
+        UploadFile::Reader { filename, reader, .. } => {
+            let source =
+                reader.take().ok_or_else(|| Error::UploadSourceConsumed(filename.clone()))?;
+            ReaderStream::with_capacity(source, UPLOAD_CHUNK_SIZE).boxed()
+        }
+    };
+
+    let Some(reporter) = reporter else {
+        return Ok(chunks);
+    };
+
+    let total = file.size();
+    let mut sent = 0;
+    Ok(chunks
+        .inspect_ok(move |chunk| {
+            sent += chunk.len() as u64;
+            reporter.report(UploadStage::Sending { sent, total });
+        })
+        .boxed())
+}
+
+/// Splits in-memory contents into zero-copy chunks.
+fn chunked(data: Bytes) -> BoxStream<'static, io::Result<Bytes>> {
+    let len = data.len();
+    let chunks = (0..len)
+        .step_by(UPLOAD_CHUNK_SIZE)
+        .map(move |start| Ok(data.slice(start..len.min(start + UPLOAD_CHUNK_SIZE))));
+    futures_util::stream::iter(chunks).boxed()
+}
+
 /// Step 3: subscribe to the attachment-processing SSE endpoint and wait
 /// until the server finishes processing all files.
+///
+/// Returns the final processing event of every file (same order as
+/// `file_uuids`), or `None` for files the stream did not report on.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(files = file_uuids.len()))
+)]
 async fn wait_for_processing(
     http: &HttpClient,
     file_uuids: &[String],
     timeout: Duration,
-) -> Result<()> {
+    reporters: &[Option<FileProgress>],
+) -> Result<Vec<Option<ProcessingEvent>>> {
     let body = ProcessingSubscribeRequest { file_uuids: file_uuids.to_vec() };
 
     let sse_fut = http
@@ -200,11 +524,251 @@ async fn wait_for_processing(
         .error_for_status()
         .map_err(Error::AttachmentProcessing)?;
 
-    let body_fut = resp.bytes();
-    tokio::time::timeout(timeout, body_fut)
+    let frames =
+        SseFrames::with_error(resp.bytes_stream(), Error::AttachmentProcessing).all_events();
+    tokio::time::timeout(timeout, collect_processing_events(frames, file_uuids, reporters))
         .await
         .map_err(|_| Error::Timeout(timeout))?
-        .map_err(Error::AttachmentProcessing)?;
+}
 
-    Ok(())
+/// Reads processing events until every file reached a final state or the
+/// stream ends.
+///
+/// The event name is not relied on, since only the payload tells which file
+/// an event is about. Statuses not known to be final are treated as progress,
+/// so a file whose final status is spelled differently stays unreported.
+async fn collect_processing_events(
+    frames: impl Stream<Item = Result<Bytes>>,
+    file_uuids: &[String],
+    reporters: &[Option<FileProgress>],
+) -> Result<Vec<Option<ProcessingEvent>>> {
+    let mut processed: Vec<Option<ProcessingEvent>> =
+        file_uuids.iter().map(|_| None).collect();
+    let mut frames = std::pin::pin!(frames);
+    while let Some(frame) = frames.next().await {
+        // Events that are not about a single known file carry nothing to record.
+        let Ok(event) = parse_processing_event(&frame?) else { continue };
+        let Some(index) = event
+            .file_uuid
+            .as_deref()
+            .and_then(|uuid| file_uuids.iter().position(|u| u == uuid))
+        else {
+            continue;
+        };
+        if !event.is_terminal() {
+            continue;
+        }
+
+        if let Some(reporter) = &reporters[index] {
+            let stage = if event.failure_reason().is_some() {
+                UploadStage::Rejected
+            } else {
+                UploadStage::Processed
+            };
+            reporter.report(stage);
+        }
+        processed[index] = Some(event);
+
+        if processed.iter().all(Option::is_some) {
+            break;
+        }
+    }
+    Ok(processed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUIDS: [&str; 3] = ["done", "failed", "silent"];
+
+    async fn collect(body: &'static str) -> Vec<Option<ProcessingEvent>> {
+        let chunks: Vec<rquest::Result<Bytes>> = vec![Ok(Bytes::from_static(body.as_bytes()))];
+        // The stream never ends on its own, so reading must stop at
+        // `end_of_stream` or once every file is reported.
+        let bytes = futures_util::stream::iter(chunks).chain(futures_util::stream::pending());
+        let frames = SseFrames::with_error(bytes, Error::AttachmentProcessing).all_events();
+        let file_uuids: Vec<String> = UUIDS.iter().map(ToString::to_string).collect();
+        collect_processing_events(frames, &file_uuids, &[None, None, None]).await.unwrap()
+    }
+
+    fn outcome(file_uuid: &str, event: Option<&ProcessingEvent>) -> AttachmentOutcome {
+        AttachmentOutcome {
+            filename: format!("{file_uuid}.pdf"),
+            file_uuid: file_uuid.to_string(),
+            s3_object_url: format!("https://s3.example/{file_uuid}"),
+            status: processing_status(event),
+            page_count: None,
+            char_count: None,
+            truncated: false,
+        }
+    }
+
+    async fn read_all(file: &UploadFile) -> Result<Vec<u8>> {
+        let chunks: Vec<Bytes> = upload_chunks(file, None)
+            .await?
+            .try_collect()
+            .await
+            .map_err(Error::UploadSource)?;
+        Ok(chunks.concat())
+    }
+
+    #[tokio::test]
+    async fn streams_files_from_disk() {
+        let dir = std::env::temp_dir().join(format!("upload-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("scan");
+        tokio::fs::write(&path, b"%PDF-1.7\n").await.unwrap();
+
+        let file = UploadFile::from_path(&path).await.unwrap();
+        assert_eq!(file.filename(), "scan");
+        assert_eq!(file.size(), 9);
+        assert_eq!(content_type(&file).await.unwrap(), "application/pdf");
+        assert_eq!(read_all(&file).await.unwrap(), b"%PDF-1.7\n");
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+        assert!(matches!(read_all(&file).await, Err(Error::UploadSource(_))));
+        assert!(UploadFile::from_path(&path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_a_reader_only_once() {
+        let file = UploadFile::from_reader("notes.txt", &b"hello"[..], 5);
+        let clone = file.clone();
+        assert_eq!(file.size(), 5);
+        assert_eq!(content_type(&file).await.unwrap(), "text/plain");
+        assert_eq!(read_all(&file).await.unwrap(), b"hello");
+
+        for file in [&file, &clone] {
+            assert!(matches!(
+                read_all(file).await,
+                Err(Error::UploadSourceConsumed(name)) if name == "notes.txt"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn times_out_only_stalled_uploads() {
+        let timeout = Duration::from_millis(30);
+        let activity = Activity::new();
+        let sending = {
+            let activity = activity.clone();
+            async move {
+                for _ in 0..6 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    activity.touch();
+                }
+            }
+        };
+        assert!(until_stalled(sending, &activity, timeout).await.is_ok());
+
+        let stalled = until_stalled(std::future::pending::<()>(), &activity, timeout).await;
+        assert!(matches!(stalled, Err(Error::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn reports_progress_of_chunked_text() {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let handler = {
+            let updates = Arc::clone(&updates);
+            UploadProgressHandler::new(move |progress| updates.lock().unwrap().push(progress))
+        };
+        let reporter = FileProgress {
+            handler,
+            index: 1,
+            filename: "notes.txt".to_string(),
+            file_uuid: "file-1".to_string(),
+        };
+        let total = 2 * UPLOAD_CHUNK_SIZE + 100;
+        let file = UploadFile::from_text("notes.txt", "a".repeat(total));
+
+        let chunks: Vec<Bytes> = upload_chunks(&file, Some(reporter.clone()))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(chunks.iter().map(Bytes::len).sum::<usize>(), total);
+        report_all(&[Some(reporter), None], UploadStage::Processed);
+
+        let updates = updates.lock().unwrap();
+        let total = total as u64;
+        let chunk = UPLOAD_CHUNK_SIZE as u64;
+        let stages: Vec<UploadStage> = updates.iter().map(|progress| progress.stage).collect();
+        assert_eq!(
+            stages,
+            vec![
+                UploadStage::Sending { sent: chunk, total },
+                UploadStage::Sending { sent: 2 * chunk, total },
+                UploadStage::Sending { sent: total, total },
+                UploadStage::Processed,
+            ]
+        );
+        assert!(updates.iter().all(|progress| progress.index == 1
+            && progress.filename == "notes.txt"
+            && progress.file_uuid == "file-1"));
+    }
+
+    #[tokio::test]
+    async fn checks_explicit_content_type_before_upload() {
+        let file = UploadFile::from_text("notes", "hello").content_type("text/markdown");
+        assert_eq!(content_type(&file).await.unwrap(), "text/markdown");
+
+        let file = UploadFile::from_text("notes", "hello").content_type("not a mime type");
+        assert!(matches!(content_type(&file).await, Err(Error::InvalidMimeType(_))));
+    }
+
+    #[tokio::test]
+    async fn maps_processing_events_to_outcomes() {
+        let events = collect(concat!(
+            "event: message\r\ndata: {\"file_uuid\": \"done\", \"status\": \"processing\"}\r\n\r\n",
+            "event: file_processed\r\ndata: {\"file_uuid\": \"done\", \"status\": \"completed\", ",
+            "\"num_pages\": 3}\r\n\r\n",
+            "event: message\r\ndata: {\"file_uuid\": \"failed\", \"status\": \"failed\", ",
+            "\"error_message\": \"encrypted PDF\"}\r\n\r\n",
+            "event: end_of_stream\r\ndata: {}\r\n\r\n",
+        ))
+        .await;
+        assert_eq!(events[0].as_ref().and_then(|event| event.page_count), Some(3));
+
+        let outcomes: Vec<AttachmentOutcome> = UUIDS
+            .iter()
+            .zip(&events)
+            .map(|(uuid, event)| outcome(uuid, event.as_ref()))
+            .collect();
+        assert_eq!(outcomes[0].status, AttachmentStatus::Processed);
+        assert_eq!(
+            outcomes[1].status,
+            AttachmentStatus::Failed { reason: "encrypted PDF".to_string() }
+        );
+        assert_eq!(outcomes[2].status, AttachmentStatus::Unconfirmed);
+
+        assert_eq!(
+            accepted_urls(vec![outcomes[0].clone()]).unwrap(),
+            vec!["https://s3.example/done".to_string()]
+        );
+        assert!(matches!(
+            accepted_urls(vec![outcomes[0].clone(), outcomes[1].clone()]),
+            Err(Error::AttachmentRejected { reason, .. }) if reason == "encrypted PDF"
+        ));
+        assert!(matches!(
+            accepted_urls(vec![outcomes[0].clone(), outcomes[2].clone()]),
+            Err(Error::AttachmentUnconfirmed { filename }) if filename == "silent.pdf"
+        ));
+    }
+
+    #[tokio::test]
+    async fn stops_once_every_file_is_reported() {
+        let events = collect(concat!(
+            "data: {\"file_uuid\": \"silent\", \"status\": \"done\"}\r\n\r\n",
+            "event: message\r\ndata: {\"file_uuid\": \"failed\", \"status\": \"rejected\"}\r\n\r\n",
+            "event: message\r\ndata: {\"file_uuid\": \"done\", \"status\": \"success\"}\r\n\r\n",
+        ))
+        .await;
+        assert!(events.iter().all(Option::is_some));
+        assert_eq!(
+            processing_status(events[1].as_ref()),
+            AttachmentStatus::Failed { reason: "rejected".to_string() }
+        );
+    }
 }
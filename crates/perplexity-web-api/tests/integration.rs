@@ -1,4 +1,7 @@
-use perplexity_web_api::{AuthCookies, Client, SearchMode, SearchRequest, SearchResponse};
+use futures_util::StreamExt;
+use perplexity_web_api::{
+    AuthCookies, Client, SearchMode, SearchModel, SearchRequest, SearchResponse,
+};
 use std::time::Duration;
 
 const SESSION_TOKEN_ENV: &str = "PERPLEXITY_SESSION_TOKEN";
@@ -125,3 +128,36 @@ async fn perplexity_reason_with_authorization_returns_answer() {
 
     assert_response_has_answer(&response, "perplexity_reason with authorization");
 }
+
+#[tokio::test]
+#[ignore]
+async fn perplexity_rewrite_with_authorization_streams_new_answer() {
+    ensure_required_env_vars();
+
+    let client = Client::builder()
+        .cookies(auth_cookies())
+        .timeout(SEARCH_TIMEOUT)
+        .build()
+        .await
+        .expect("Failed to build authenticated client for rewrite test");
+
+    let response = client
+        .search(SearchRequest::new(SEARCH_QUERY).mode(SearchMode::Pro).incognito(true))
+        .await
+        .expect("Search before rewrite failed");
+
+    let stream = client
+        .rewrite(&response.follow_up, SearchModel::Gpt54)
+        .await
+        .expect("rewrite request failed");
+    let events: Vec<_> = stream.collect().await;
+    let answer = events
+        .into_iter()
+        .filter_map(|event| event.expect("rewrite stream failed").answer)
+        .next_back();
+
+    assert!(
+        answer.is_some_and(|answer| !answer.trim().is_empty()),
+        "rewrite returned no answer"
+    );
+}